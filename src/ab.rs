@@ -0,0 +1,86 @@
+//! The A/B comparison slots. Each slot is a snapshot of the parameters. The editor applies them
+//! by setting the parameters, whose smoothers keep switching and morphing free of clicks.
+
+use nih_plug::prelude::ParamPtr;
+use serde::{Deserialize, Serialize};
+use vizia_plug::vizia::prelude::Data;
+
+use crate::DisperserParams;
+use crate::preset::PresetValues;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Data)]
+pub enum AbSlot {
+    #[default]
+    A,
+    B,
+}
+
+impl AbSlot {
+    pub fn other(&self) -> Self {
+        match self {
+            AbSlot::A => AbSlot::B,
+            AbSlot::B => AbSlot::A,
+        }
+    }
+
+    /// The morph position that plays this slot unchanged.
+    pub fn morph_position(&self) -> f32 {
+        match self {
+            AbSlot::A => 0.0,
+            AbSlot::B => 1.0,
+        }
+    }
+}
+
+/// Persisted in the plugin state, so the comparison survives reopening the project.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Data)]
+#[serde(default)]
+pub struct AbSlots {
+    /// The slot the parameters were last switched to. Changes to the parameters belong to it.
+    pub active: AbSlot,
+    /// `None` until the slot is first used, switching to an empty slot copies the other one.
+    pub a: Option<PresetValues>,
+    pub b: Option<PresetValues>,
+    /// 0 plays A and 1 plays B.
+    pub morph: f32,
+}
+
+impl AbSlots {
+    pub fn slot(&self, slot: AbSlot) -> Option<PresetValues> {
+        match slot {
+            AbSlot::A => self.a,
+            AbSlot::B => self.b,
+        }
+    }
+
+    pub fn set_slot(&mut self, slot: AbSlot, values: PresetValues) {
+        match slot {
+            AbSlot::A => self.a = Some(values),
+            AbSlot::B => self.b = Some(values),
+        }
+    }
+
+    /// Stores the current parameters in the active slot. While morphing the parameters are a mix
+    /// of both slots, so they're only stored when the morph is at the active slot's end.
+    pub fn store_active(&mut self, params: &DisperserParams) {
+        if self.morph == self.active.morph_position() {
+            self.set_slot(self.active, PresetValues::from_params(params));
+        }
+    }
+
+    /// The normalized parameter values for the current morph position. Frequency and spread are
+    /// interpolated in the normalized domain, so the sweep follows their skewed ranges. The
+    /// amount switches over halfway.
+    pub fn morphed_values(&self, params: &DisperserParams) -> Option<[(ParamPtr, f32); 3]> {
+        let a = self.a?.normalized_values(params);
+        let b = self.b?.normalized_values(params);
+        let t = self.morph.clamp(0.0, 1.0);
+
+        let lerp = |i: usize| (a[i].0, a[i].1 + (b[i].1 - a[i].1) * t);
+        Some([
+            lerp(0),
+            lerp(1),
+            (a[2].0, if t < 0.5 { a[2].1 } else { b[2].1 }),
+        ])
+    }
+}
@@ -0,0 +1,296 @@
+//! A model of the disperser's allpass cascade, used by the analysis views. The coefficients are
+//! computed in `f64` with the same design `i_am_dsp`'s `Disperser` uses, and the tests check the
+//! model against the `Disperser`'s impulse response.
+
+use realfft::num_complex::Complex;
+use std::f64::consts::TAU;
+use vizia_plug::vizia::prelude::Data;
+
+/// Poles with a radius above this are considered close enough to instability to warn about.
+pub const NEAR_UNSTABLE_RADIUS: f64 = 0.999;
+
+/// The parameters that determine the shape of the cascade.
+#[derive(Debug, Clone, Copy, PartialEq, Data)]
+pub struct ChainSettings {
+    pub frequency: f32,
+    pub spread: f32,
+    pub amount: usize,
+    pub sample_rate: f32,
+}
+
+/// A single second order allpass section, normalized so `a0 == 1`. The numerator is the
+/// denominator reversed, i.e. `b0 = a2`, `b1 = a1` and `b2 = 1`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AllpassStage {
+    pub a1: f64,
+    pub a2: f64,
+}
+
+impl AllpassStage {
+    /// The RBJ cookbook allpass centered on `frequency` with a bandwidth of `bandwidth` Hz, like
+    /// every stage of the `Disperser`.
+    pub fn new(frequency: f64, bandwidth: f64, sample_rate: f64) -> Self {
+        let frequency = frequency.clamp(1.0, sample_rate * 0.49);
+        let q = frequency / bandwidth.max(1e-3);
+        let w0 = TAU * frequency / sample_rate;
+        let alpha = w0.sin() / (2.0 * q);
+        let a0 = 1.0 + alpha;
+
+        Self {
+            a1: -2.0 * w0.cos() / a0,
+            a2: (1.0 - alpha) / a0,
+        }
+    }
+
+    /// The upper of the two conjugate poles. The lower one is its mirror image.
+    pub fn pole(&self) -> Complex<f64> {
+        // Roots of z^2 + a1 z + a2
+        let discriminant = self.a1 * self.a1 / 4.0 - self.a2;
+        if discriminant >= 0.0 {
+            // Two real poles, we'll return the one furthest from the origin
+            let root = discriminant.sqrt();
+            let (p1, p2) = (-self.a1 / 2.0 + root, -self.a1 / 2.0 - root);
+            Complex::new(if p1.abs() >= p2.abs() { p1 } else { p2 }, 0.0)
+        } else {
+            Complex::new(-self.a1 / 2.0, (-discriminant).sqrt())
+        }
+    }
+
+    /// Both poles. For complex poles these are a conjugate pair.
+    pub fn poles(&self) -> [Complex<f64>; 2] {
+        let discriminant = self.a1 * self.a1 / 4.0 - self.a2;
+        if discriminant >= 0.0 {
+            let root = discriminant.sqrt();
+            [
+                Complex::new(-self.a1 / 2.0 + root, 0.0),
+                Complex::new(-self.a1 / 2.0 - root, 0.0),
+            ]
+        } else {
+            let pole = self.pole();
+            [pole, pole.conj()]
+        }
+    }
+
+    /// The zeros of an allpass are the poles reflected through the unit circle.
+    pub fn zero(&self) -> Complex<f64> {
+        let pole = self.pole();
+        if pole.norm_sqr() == 0.0 {
+            pole
+        } else {
+            pole / pole.norm_sqr()
+        }
+    }
+
+    pub fn pole_radius(&self) -> f64 {
+        self.pole().norm()
+    }
+
+    /// The complex frequency response at the normalized angular frequency `omega`, evaluated
+    /// straight from the coefficients.
+    pub fn response(&self, omega: f64) -> Complex<f64> {
+        let z1 = Complex::from_polar(1.0, -omega);
+        let z2 = z1 * z1;
+        let numerator = self.a2 + self.a1 * z1 + z2;
+        let denominator = 1.0 + self.a1 * z1 + self.a2 * z2;
+
+        numerator / denominator
+    }
+
+    /// The unwrapped phase at `omega`, in radians. This goes from 0 at DC to -2π at Nyquist.
+    ///
+    /// Taking the argument of [`response()`][Self::response()] would wrap around at ±π, so
+    /// instead the section is split into two first order allpasses, one per pole. Each of those
+    /// has a phase of `-ω - 2 atan(r sin(ω - θ) / (1 - r cos(ω - θ)))`, which never wraps for a
+    /// stable pole.
+    pub fn phase(&self, omega: f64) -> f64 {
+        self.poles()
+            .iter()
+            .map(|pole| {
+                let (r, theta) = pole.to_polar();
+                -omega - 2.0 * (r * (omega - theta).sin()).atan2(1.0 - r * (omega - theta).cos())
+            })
+            .sum()
+    }
+
+    /// The group delay at `omega`, in samples. Like the phase this is the sum of two first order
+    /// sections, each of which has a delay of `(1 - r²) / (1 - 2r cos(ω - θ) + r²)`.
+    pub fn group_delay(&self, omega: f64) -> f64 {
+        self.poles()
+            .iter()
+            .map(|pole| {
+                let (r, theta) = pole.to_polar();
+                (1.0 - r * r) / (1.0 - 2.0 * r * (omega - theta).cos() + r * r)
+            })
+            .sum()
+    }
+}
+
+/// The full cascade for a set of [`ChainSettings`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AllpassChain {
+    pub stages: Vec<AllpassStage>,
+    pub sample_rate: f64,
+}
+
+impl Default for AllpassChain {
+    fn default() -> Self {
+        Self {
+            stages: Vec::new(),
+            sample_rate: 44100.0,
+        }
+    }
+}
+
+impl AllpassChain {
+    pub fn new(settings: ChainSettings) -> Self {
+        let sample_rate = settings.sample_rate.max(1.0) as f64;
+        // The `Disperser` runs the same stage `amount` times
+        let stage = AllpassStage::new(
+            settings.frequency as f64,
+            settings.spread as f64,
+            sample_rate,
+        );
+
+        Self {
+            stages: vec![stage; settings.amount],
+            sample_rate,
+        }
+    }
+
+    pub fn nyquist(&self) -> f64 {
+        self.sample_rate / 2.0
+    }
+
+    /// Converts a frequency in Hz to a normalized angular frequency.
+    pub fn omega(&self, frequency: f64) -> f64 {
+        TAU * frequency / self.sample_rate
+    }
+
+    /// The unwrapped phase of the whole cascade at `frequency`, in radians.
+    pub fn phase(&self, frequency: f64) -> f64 {
+        let omega = self.omega(frequency);
+        self.stages.iter().map(|stage| stage.phase(omega)).sum()
+    }
+
+    /// The group delay of the whole cascade at `frequency`, in milliseconds.
+    pub fn group_delay_ms(&self, frequency: f64) -> f64 {
+        let omega = self.omega(frequency);
+        let samples: f64 = self
+            .stages
+            .iter()
+            .map(|stage| stage.group_delay(omega))
+            .sum();
+
+        samples / self.sample_rate * 1000.0
+    }
+
+    /// Runs a unit impulse through the cascade and returns the first `length` samples.
+    pub fn impulse_response(&self, length: usize) -> Vec<f32> {
+        let mut signal = vec![0.0f64; length];
+        if let Some(first) = signal.first_mut() {
+            *first = 1.0;
+        }
+
+        // Transposed direct form II, one stage at a time
+        for stage in &self.stages {
+            let (b0, b1, b2) = (stage.a2, stage.a1, 1.0);
+            let (mut s1, mut s2) = (0.0, 0.0);
+            for sample in signal.iter_mut() {
+                let input = *sample;
+                let output = b0 * input + s1;
+                s1 = b1 * input - stage.a1 * output + s2;
+                s2 = b2 * input - stage.a2 * output;
+                *sample = output;
+            }
+        }
+
+        signal.into_iter().map(|sample| sample as f32).collect()
+    }
+
+    /// The magnitude of the whole cascade at `frequency`, in decibels. This should be 0 dB
+    /// everywhere, give or take rounding errors.
+    pub fn magnitude_db(&self, frequency: f64) -> f64 {
+        let omega = self.omega(frequency);
+        let gain: f64 = self
+            .stages
+            .iter()
+            .map(|stage| stage.response(omega).norm())
+            .product();
+
+        20.0 * gain.max(1e-12).log10()
+    }
+
+    /// Whether any stage has poles close to the unit circle.
+    pub fn is_near_unstable(&self) -> bool {
+        self.stages
+            .iter()
+            .any(|stage| stage.pole_radius() >= NEAR_UNSTABLE_RADIUS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use i_am_dsp::{
+        Effect, ProcessContext as DspContext, ProcessInfos, prelude::Disperser,
+        real_time_demo::SimpleContext,
+    };
+
+    /// The impulse response of the plugin's actual `Disperser`.
+    fn disperser_impulse_response(settings: ChainSettings, length: usize) -> Vec<f32> {
+        let mut info = ProcessInfos::new();
+        info.sample_rate = settings.sample_rate as usize;
+        let mut dsp_ctx: Box<dyn DspContext> = Box::new(SimpleContext {
+            info,
+            midi_events: Vec::new(),
+        });
+
+        let mut disperser = Disperser::<2>::new(settings.sample_rate as usize);
+        disperser.set_filter_parameters(settings.frequency, settings.spread);
+        disperser.set_biquad_count(settings.amount);
+
+        let other_inputs: &[&[f32; 2]] = &[];
+        (0..length)
+            .map(|i| {
+                let mut frame = [if i == 0 { 1.0 } else { 0.0 }; 2];
+                disperser.process(&mut frame, other_inputs, &mut dsp_ctx);
+                frame[0]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn model_matches_disperser() {
+        for settings in [
+            ChainSettings {
+                frequency: 1145.0,
+                spread: 1145.0,
+                amount: 8,
+                sample_rate: 48000.0,
+            },
+            ChainSettings {
+                frequency: 200.0,
+                spread: 50.0,
+                amount: 40,
+                sample_rate: 44100.0,
+            },
+            ChainSettings {
+                frequency: 8000.0,
+                spread: 2000.0,
+                amount: 100,
+                sample_rate: 96000.0,
+            },
+        ] {
+            let length = 4096;
+            let expected = disperser_impulse_response(settings, length);
+            let actual = AllpassChain::new(settings).impulse_response(length);
+
+            let max_error = expected
+                .iter()
+                .zip(&actual)
+                .map(|(expected, actual)| (expected - actual).abs())
+                .fold(0.0, f32::max);
+            assert!(max_error < 1e-3, "{settings:?}: off by {max_error}");
+        }
+    }
+}
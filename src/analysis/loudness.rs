@@ -0,0 +1,363 @@
+//! ITU-R BS.1770 / EBU R128 loudness metering with K-weighting and gating.
+
+use atomic_float::AtomicF32;
+use nih_plug::util;
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Loudness below this is ignored by the integrated measurement, in LUFS.
+const ABSOLUTE_GATE: f64 = -70.0;
+/// The relative gate sits this many LU below the ungated integrated loudness.
+const RELATIVE_GATE: f64 = -10.0;
+
+/// Every measurement is built from 100 ms sub-blocks.
+const SUB_BLOCK_SECONDS: f64 = 0.1;
+const MOMENTARY_SUB_BLOCKS: usize = 4;
+const SHORT_TERM_SUB_BLOCKS: usize = 30;
+
+/// The integrated loudness is kept as a histogram from -70 to +30 LUFS in 0.1 LU steps, so it
+/// can run forever without allocating.
+const HISTOGRAM_BINS: usize = 1000;
+const HISTOGRAM_STEP: f64 = 0.1;
+
+/// The latest readings of a single [`LoudnessMeter`], in LUFS. Momentary loudness covers the last
+/// 400 ms, short-term the last 3 s and integrated everything since the last reset.
+pub struct MeterReadings {
+    pub momentary: AtomicF32,
+    pub short_term: AtomicF32,
+    pub integrated: AtomicF32,
+}
+
+impl Default for MeterReadings {
+    fn default() -> Self {
+        Self {
+            momentary: AtomicF32::new(util::MINUS_INFINITY_DB),
+            short_term: AtomicF32::new(util::MINUS_INFINITY_DB),
+            integrated: AtomicF32::new(util::MINUS_INFINITY_DB),
+        }
+    }
+}
+
+/// The input and output loudness, shared between the audio thread and the editor.
+#[derive(Default)]
+pub struct LoudnessReadings {
+    pub input: MeterReadings,
+    pub output: MeterReadings,
+    /// Set by the editor, picked up by the audio thread at the start of the next buffer.
+    pub reset_requested: AtomicBool,
+}
+
+impl LoudnessReadings {
+    pub fn request_reset(&self) {
+        self.reset_requested.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns whether a reset was requested since the last call.
+    pub fn take_reset_request(&self) -> bool {
+        self.reset_requested.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// A direct form I biquad, used for the two K-weighting stages.
+#[derive(Clone, Copy, Default)]
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    x1: f64,
+    x2: f64,
+    y1: f64,
+    y2: f64,
+}
+
+impl Biquad {
+    /// The high shelf that models the acoustic effect of the head.
+    fn k_weighting_shelf(sample_rate: f64) -> Self {
+        let f0 = 1681.974450955533;
+        let gain_db = 3.999843853973347;
+        let q = 0.7071752369554196;
+
+        let k = (PI * f0 / sample_rate).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+
+        Self {
+            b0: (vh + vb * k / q + k * k) / a0,
+            b1: 2.0 * (k * k - vh) / a0,
+            b2: (vh - vb * k / q + k * k) / a0,
+            a1: 2.0 * (k * k - 1.0) / a0,
+            a2: (1.0 - k / q + k * k) / a0,
+            ..Default::default()
+        }
+    }
+
+    /// The revised low-frequency B-curve high-pass.
+    fn k_weighting_highpass(sample_rate: f64) -> Self {
+        let f0 = 38.13547087602444;
+        let q = 0.5003270373238773;
+
+        let k = (PI * f0 / sample_rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+
+        Self {
+            b0: 1.0,
+            b1: -2.0,
+            b2: 1.0,
+            a1: 2.0 * (k * k - 1.0) / a0,
+            a2: (1.0 - k / q + k * k) / a0,
+            ..Default::default()
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b0 * x + self.b1 * self.x1 + self.b2 * self.x2
+            - self.a1 * self.y1
+            - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+
+        y
+    }
+}
+
+/// Measures the momentary, short-term and integrated loudness of a stereo signal. All of the
+/// state is preallocated, so [`process()`][Self::process()] is safe to call on the audio thread.
+pub struct LoudnessMeter {
+    filters: [[Biquad; 2]; 2],
+    sub_block_len: usize,
+    sub_block_pos: usize,
+    sub_block_sum: f64,
+
+    /// The mean square of the last sub-blocks, used as a ring buffer.
+    sub_blocks: [f64; SHORT_TERM_SUB_BLOCKS],
+    sub_block_idx: usize,
+    sub_blocks_filled: usize,
+
+    histogram: Box<[u32; HISTOGRAM_BINS]>,
+    /// The mean square power belonging to the center of every histogram bin.
+    histogram_power: Box<[f64; HISTOGRAM_BINS]>,
+
+    momentary: f32,
+    short_term: f32,
+    integrated: f32,
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: f32) -> Self {
+        let sample_rate = sample_rate as f64;
+        let mut histogram_power = Box::new([0.0; HISTOGRAM_BINS]);
+        for (i, power) in histogram_power.iter_mut().enumerate() {
+            let loudness = ABSOLUTE_GATE + (i as f64 + 0.5) * HISTOGRAM_STEP;
+            *power = loudness_to_power(loudness);
+        }
+
+        let filters = [
+            Biquad::k_weighting_shelf(sample_rate),
+            Biquad::k_weighting_highpass(sample_rate),
+        ];
+
+        Self {
+            filters: [filters, filters],
+            sub_block_len: (sample_rate * SUB_BLOCK_SECONDS).round().max(1.0) as usize,
+            sub_block_pos: 0,
+            sub_block_sum: 0.0,
+
+            sub_blocks: [0.0; SHORT_TERM_SUB_BLOCKS],
+            sub_block_idx: 0,
+            sub_blocks_filled: 0,
+
+            histogram: Box::new([0; HISTOGRAM_BINS]),
+            histogram_power,
+
+            momentary: util::MINUS_INFINITY_DB,
+            short_term: util::MINUS_INFINITY_DB,
+            integrated: util::MINUS_INFINITY_DB,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for channel in &mut self.filters {
+            for filter in channel {
+                filter.x1 = 0.0;
+                filter.x2 = 0.0;
+                filter.y1 = 0.0;
+                filter.y2 = 0.0;
+            }
+        }
+        self.sub_block_pos = 0;
+        self.sub_block_sum = 0.0;
+        self.sub_blocks = [0.0; SHORT_TERM_SUB_BLOCKS];
+        self.sub_block_idx = 0;
+        self.sub_blocks_filled = 0;
+        self.histogram.fill(0);
+
+        self.momentary = util::MINUS_INFINITY_DB;
+        self.short_term = util::MINUS_INFINITY_DB;
+        self.integrated = util::MINUS_INFINITY_DB;
+    }
+
+    pub fn process(&mut self, left: f32, right: f32) {
+        let mut sum = 0.0;
+        for (channel, sample) in self.filters.iter_mut().zip([left, right]) {
+            let weighted = channel
+                .iter_mut()
+                .fold(sample as f64, |x, filter| filter.process(x));
+            sum += weighted * weighted;
+        }
+
+        self.sub_block_sum += sum;
+        self.sub_block_pos += 1;
+        if self.sub_block_pos >= self.sub_block_len {
+            let mean_square = self.sub_block_sum / self.sub_block_len as f64;
+            self.sub_block_pos = 0;
+            self.sub_block_sum = 0.0;
+            self.push_sub_block(mean_square);
+        }
+    }
+
+    fn push_sub_block(&mut self, mean_square: f64) {
+        self.sub_blocks[self.sub_block_idx] = mean_square;
+        self.sub_block_idx = (self.sub_block_idx + 1) % SHORT_TERM_SUB_BLOCKS;
+        self.sub_blocks_filled = (self.sub_blocks_filled + 1).min(SHORT_TERM_SUB_BLOCKS);
+
+        if self.sub_blocks_filled >= MOMENTARY_SUB_BLOCKS {
+            // Every new sub-block completes a 400 ms gating block with 75% overlap
+            let momentary_power = self.mean_of_last(MOMENTARY_SUB_BLOCKS);
+            let momentary = power_to_loudness(momentary_power);
+            self.momentary = momentary as f32;
+
+            if momentary > ABSOLUTE_GATE {
+                let bin = ((momentary - ABSOLUTE_GATE) / HISTOGRAM_STEP) as usize;
+                self.histogram[bin.min(HISTOGRAM_BINS - 1)] += 1;
+                self.integrated = self.compute_integrated() as f32;
+            }
+        }
+
+        if self.sub_blocks_filled >= SHORT_TERM_SUB_BLOCKS {
+            self.short_term = power_to_loudness(self.mean_of_last(SHORT_TERM_SUB_BLOCKS)) as f32;
+        }
+    }
+
+    fn mean_of_last(&self, count: usize) -> f64 {
+        let len = SHORT_TERM_SUB_BLOCKS;
+        (1..=count)
+            .map(|i| self.sub_blocks[(self.sub_block_idx + len - i) % len])
+            .sum::<f64>()
+            / count as f64
+    }
+
+    fn compute_integrated(&self) -> f64 {
+        let gated_mean = |threshold: f64| {
+            let (count, power) = self
+                .histogram
+                .iter()
+                .zip(self.histogram_power.iter())
+                .enumerate()
+                .filter(|(i, _)| ABSOLUTE_GATE + (*i as f64 + 0.5) * HISTOGRAM_STEP > threshold)
+                .fold((0u64, 0.0), |(count, power), (_, (&n, &p))| {
+                    (count + n as u64, power + n as f64 * p)
+                });
+
+            if count == 0 {
+                None
+            } else {
+                Some(power / count as f64)
+            }
+        };
+
+        let Some(ungated) = gated_mean(ABSOLUTE_GATE) else {
+            return util::MINUS_INFINITY_DB as f64;
+        };
+        let relative_gate = power_to_loudness(ungated) + RELATIVE_GATE;
+
+        gated_mean(relative_gate)
+            .map(power_to_loudness)
+            .unwrap_or(util::MINUS_INFINITY_DB as f64)
+    }
+
+    /// Stores the current readings so the editor can pick them up.
+    pub fn publish(&self, readings: &MeterReadings) {
+        readings.momentary.store(self.momentary, Ordering::Relaxed);
+        readings
+            .short_term
+            .store(self.short_term, Ordering::Relaxed);
+        readings
+            .integrated
+            .store(self.integrated, Ordering::Relaxed);
+    }
+}
+
+fn power_to_loudness(power: f64) -> f64 {
+    if power <= 0.0 {
+        util::MINUS_INFINITY_DB as f64
+    } else {
+        (-0.691 + 10.0 * power.log10()).max(util::MINUS_INFINITY_DB as f64)
+    }
+}
+
+fn loudness_to_power(loudness: f64) -> f64 {
+    10f64.powf((loudness + 0.691) / 10.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    /// Feeds `seconds` of a sine with a peak level of `level_dbfs` to both channels.
+    fn feed_sine(meter: &mut LoudnessMeter, frequency: f32, level_dbfs: f32, seconds: f32) {
+        let amplitude = util::db_to_gain(level_dbfs);
+        let num_samples = (seconds * SAMPLE_RATE).round() as usize;
+        for i in 0..num_samples {
+            let phase = std::f32::consts::TAU * frequency * i as f32 / SAMPLE_RATE;
+            let sample = amplitude * phase.sin();
+            meter.process(sample, sample);
+        }
+    }
+
+    #[test]
+    fn sine_at_minus_20_dbfs_reads_minus_20_lufs() {
+        let mut meter = LoudnessMeter::new(SAMPLE_RATE);
+        feed_sine(&mut meter, 997.0, -20.0, 10.0);
+
+        assert!((meter.momentary + 20.0).abs() < 0.1, "{}", meter.momentary);
+        assert!(
+            (meter.short_term + 20.0).abs() < 0.1,
+            "{}",
+            meter.short_term
+        );
+        assert!(
+            (meter.integrated + 20.0).abs() < 0.1,
+            "{}",
+            meter.integrated
+        );
+    }
+
+    /// EBU Tech 3341 test case 4. The quiet parts fall below the absolute and the relative gate,
+    /// so they don't pull the integrated loudness down.
+    #[test]
+    fn integrated_loudness_gates_quiet_parts() {
+        let mut meter = LoudnessMeter::new(SAMPLE_RATE);
+        for (level, seconds) in [
+            (-72.0, 10.0),
+            (-36.0, 10.0),
+            (-23.0, 60.0),
+            (-36.0, 10.0),
+            (-72.0, 10.0),
+        ] {
+            feed_sine(&mut meter, 1000.0, level, seconds);
+        }
+
+        assert!(
+            (meter.integrated + 23.0).abs() < 0.1,
+            "{}",
+            meter.integrated
+        );
+    }
+}
@@ -0,0 +1,4 @@
+pub mod allpass;
+pub mod loudness;
+pub mod peak;
+pub mod taps;
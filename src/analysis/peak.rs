@@ -0,0 +1,84 @@
+//! Per-channel sample peak metering for the output, with clip detection and adjustable ballistics.
+
+use atomic_float::AtomicF32;
+use nih_plug::util;
+use std::sync::atomic::Ordering;
+
+/// How long the editor holds the peak marker by default, in milliseconds.
+pub const DEFAULT_PEAK_HOLD_MS: f32 = 600.0;
+/// The default time it takes for the level to decay by 12 dB, in milliseconds.
+pub const DEFAULT_PEAK_DECAY_MS: f32 = 150.0;
+
+/// The state of the output peak meter, shared between the audio thread and the editor.
+pub struct PeakMeterState {
+    /// The decaying peak level per channel, as a linear gain.
+    pub levels: [AtomicF32; 2],
+    /// The highest sample peak per channel since the last reset, as a linear gain.
+    pub max_peaks: [AtomicF32; 2],
+}
+
+impl Default for PeakMeterState {
+    fn default() -> Self {
+        Self {
+            levels: [AtomicF32::new(0.0), AtomicF32::new(0.0)],
+            max_peaks: [AtomicF32::new(0.0), AtomicF32::new(0.0)],
+        }
+    }
+}
+
+impl PeakMeterState {
+    /// The level of a channel in decibels.
+    pub fn level_db(&self, channel: usize) -> f32 {
+        util::gain_to_db(self.levels[channel].load(Ordering::Relaxed))
+    }
+
+    /// The highest peak of both channels since the last reset, in decibels.
+    pub fn max_peak_db(&self) -> f32 {
+        let max_peak = self
+            .max_peaks
+            .iter()
+            .map(|peak| peak.load(Ordering::Relaxed))
+            .fold(0.0, f32::max);
+
+        util::gain_to_db(max_peak)
+    }
+
+    /// Whether any sample went over 0 dBFS since the last reset.
+    pub fn is_clipped(&self) -> bool {
+        self.max_peaks
+            .iter()
+            .any(|peak| peak.load(Ordering::Relaxed) > 1.0)
+    }
+
+    /// Clears the clip indicator and the max peak readout. The audio thread may race with this,
+    /// but at worst that means the new maximum contains a peak from the current buffer.
+    pub fn reset_max_peaks(&self) {
+        for peak in &self.max_peaks {
+            peak.store(0.0, Ordering::Relaxed);
+        }
+    }
+
+    /// Feeds the sample peaks of a single buffer into the meter. `decay_weight` is the per-sample
+    /// decay coefficient.
+    pub fn update(&self, block_peaks: [f32; 2], num_samples: usize, decay_weight: f32) {
+        let block_weight = decay_weight.powi(num_samples as i32);
+        for ((level, max_peak), peak) in self.levels.iter().zip(&self.max_peaks).zip(block_peaks) {
+            let current_level = level.load(Ordering::Relaxed);
+            let new_level = if peak > current_level {
+                peak
+            } else {
+                current_level * block_weight + peak * (1.0 - block_weight)
+            };
+            level.store(new_level, Ordering::Relaxed);
+
+            if peak > max_peak.load(Ordering::Relaxed) {
+                max_peak.store(peak, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Computes the per-sample weight that makes a peak meter decay by 12 dB in `decay_ms`.
+pub fn decay_weight(sample_rate: f32, decay_ms: f32) -> f32 {
+    0.25f64.powf((sample_rate as f64 * decay_ms.max(1.0) as f64 / 1000.0).recip()) as f32
+}
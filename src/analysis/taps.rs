@@ -0,0 +1,188 @@
+//! Lock-free sample rings that carry audio from the processing thread to the editor.
+
+use atomic_float::AtomicF32;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How many frames each tap keeps around. This is enough for a couple of GUI frames at 192 kHz.
+const TAP_CAPACITY: usize = 1 << 15;
+
+/// A single-producer ring of frames with `CHANNELS` samples each. The audio thread pushes, the
+/// editor reads whatever it has not seen yet. Reads may race with writes, which is fine for
+/// drawing purposes.
+///
+/// The channels are interleaved and share a single write position, so a reader always gets whole
+/// frames even if it fell behind and the ring overran.
+pub struct SampleRing<const CHANNELS: usize = 1> {
+    samples: Box<[AtomicF32]>,
+    mask: usize,
+    written: AtomicUsize,
+}
+
+impl<const CHANNELS: usize> SampleRing<CHANNELS> {
+    /// Creates a ring holding at least `capacity` frames, rounded up to a power of two.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.next_power_of_two();
+        Self {
+            samples: (0..capacity * CHANNELS)
+                .map(|_| AtomicF32::new(0.0))
+                .collect(),
+            mask: capacity - 1,
+            written: AtomicUsize::new(0),
+        }
+    }
+
+    /// The number of frames the ring holds.
+    pub fn capacity(&self) -> usize {
+        self.mask + 1
+    }
+
+    /// Pushes a single frame. Never allocates or blocks, so this is safe to call from `process()`.
+    pub fn push_frame(&self, frame: [f32; CHANNELS]) {
+        let pos = self.written.load(Ordering::Relaxed);
+        let start = (pos & self.mask) * CHANNELS;
+        for (slot, sample) in self.samples[start..start + CHANNELS].iter().zip(frame) {
+            slot.store(sample, Ordering::Relaxed);
+        }
+        self.written.store(pos.wrapping_add(1), Ordering::Release);
+    }
+
+    /// The total number of frames pushed so far. Wraps around on overflow.
+    pub fn written(&self) -> usize {
+        self.written.load(Ordering::Acquire)
+    }
+
+    /// Advances the write position by `frames` without writing anything. This keeps the ring in
+    /// step with another one while nothing reads it, the skipped frames hold stale samples.
+    pub fn skip(&self, frames: usize) {
+        let pos = self.written.load(Ordering::Relaxed);
+        self.written
+            .store(pos.wrapping_add(frames), Ordering::Release);
+    }
+
+    /// The range of frames pushed after the position `since` that are still in the ring, as a
+    /// start position and a length, along with the current position.
+    fn frames_since(&self, since: usize) -> (usize, usize, usize) {
+        let written = self.written();
+        let available = written.wrapping_sub(since).min(self.capacity());
+        (written.wrapping_sub(available), available, written)
+    }
+
+    /// Calls `f` with `count` frames starting at the position `start`.
+    fn for_each_frame(&self, start: usize, count: usize, mut f: impl FnMut(&[AtomicF32])) {
+        for i in 0..count {
+            let frame_start = (start.wrapping_add(i) & self.mask) * CHANNELS;
+            f(&self.samples[frame_start..frame_start + CHANNELS]);
+        }
+    }
+
+    /// Appends `count` frames starting at the position `start` to `out`, downmixed to mono.
+    fn read_mono(&self, start: usize, count: usize, out: &mut Vec<f32>) {
+        self.for_each_frame(start, count, |frame| {
+            let sum: f32 = frame
+                .iter()
+                .map(|sample| sample.load(Ordering::Relaxed))
+                .sum();
+            out.push(sum / CHANNELS as f32);
+        });
+    }
+
+    /// Appends every frame pushed after the position `since` to `out`, downmixed to mono, and
+    /// returns the new position. If the reader fell behind by more than the capacity, the oldest
+    /// frames are lost.
+    pub fn read_since(&self, since: usize, out: &mut Vec<f32>) -> usize {
+        let (start, count, written) = self.frames_since(since);
+        self.read_mono(start, count, out);
+
+        written
+    }
+}
+
+impl SampleRing<1> {
+    /// Pushes a single sample, see [`Self::push_frame()`].
+    pub fn push(&self, sample: f32) {
+        self.push_frame([sample]);
+    }
+}
+
+impl SampleRing<2> {
+    /// Like [`Self::read_since()`], but keeps the channels apart. Every `left[i]` matches
+    /// `right[i]` since both come from the same frame.
+    pub fn read_stereo_since(
+        &self,
+        since: usize,
+        left: &mut Vec<f32>,
+        right: &mut Vec<f32>,
+    ) -> usize {
+        let (start, count, written) = self.frames_since(since);
+        self.for_each_frame(start, count, |frame| {
+            left.push(frame[0].load(Ordering::Relaxed));
+            right.push(frame[1].load(Ordering::Relaxed));
+        });
+
+        written
+    }
+}
+
+/// The signals the editor can look at, tapped before and after the disperser. `pre` is a mono
+/// sum of the input, `post` keeps the stereo image of the output. The audio thread keeps both at
+/// the same position, either by pushing to both or by [skipping][SampleRing::skip()] `pre` while
+/// nothing reads it, so a position in one is the same point in time in the other.
+pub struct SignalTaps {
+    pub pre: SampleRing,
+    pub post: SampleRing<2>,
+    pub sample_rate: AtomicF32,
+}
+
+impl Default for SignalTaps {
+    fn default() -> Self {
+        Self {
+            pre: SampleRing::new(TAP_CAPACITY),
+            post: SampleRing::new(TAP_CAPACITY),
+            sample_rate: AtomicF32::new(44100.0),
+        }
+    }
+}
+
+impl SignalTaps {
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate.load(Ordering::Relaxed)
+    }
+
+    /// The position both rings have reached. One of them may be a little ahead if this raced
+    /// with the audio thread.
+    pub fn written(&self) -> usize {
+        self.positions().0
+    }
+
+    /// [`Self::written()`] and how many frames the ring that's ahead has pushed beyond it.
+    fn positions(&self) -> (usize, usize) {
+        let (pre, post) = (self.pre.written(), self.post.written());
+        let post_lead = post.wrapping_sub(pre) as isize;
+        if post_lead < 0 {
+            (post, post_lead.unsigned_abs())
+        } else {
+            (pre, post_lead as usize)
+        }
+    }
+
+    /// Reads the mono pre and post signals in lockstep, so `post[i]` is the processed version of
+    /// `pre[i]`. Both are read by position, so this holds even if the reader fell behind and the
+    /// oldest frames were lost.
+    pub fn read_pre_post_since(
+        &self,
+        since: usize,
+        pre: &mut Vec<f32>,
+        post: &mut Vec<f32>,
+    ) -> usize {
+        let (written, lead) = self.positions();
+        // The ring that's ahead has already overwritten that many more of the oldest frames
+        let count = written
+            .wrapping_sub(since)
+            .min(TAP_CAPACITY.saturating_sub(lead));
+        let start = written.wrapping_sub(count);
+        self.pre.read_mono(start, count, pre);
+        self.post.read_mono(start, count, post);
+
+        written
+    }
+}
@@ -0,0 +1,93 @@
+//! Settings that are shared by every instance of the plugin, as opposed to the plugin state which
+//! is stored per instance by the host. These live in a JSON file in the user's config directory.
+
+use nih_plug::nih_log;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::localize::Language;
+use crate::theme::Theme;
+use crate::widgets::params_knob::KnobSettings;
+
+const CONFIG_DIR_NAME: &str = "im_disperser";
+const CONFIG_FILE_NAME: &str = "config.json";
+
+/// Declares [`GlobalConfig`] along with its `merge_changes()`, so every field that's stored is
+/// also merged with the file on disk without having to be listed twice.
+macro_rules! global_config {
+    ($($(#[$attr:meta])* $field:ident: $ty:ty,)*) => {
+        #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+        #[serde(default)]
+        pub struct GlobalConfig {
+            $($(#[$attr])* pub $field: $ty,)*
+        }
+
+        impl GlobalConfig {
+            /// Copies every setting that differs between `self` and `base` into `target`.
+            fn merge_changes(&self, base: &Self, target: &mut Self) {
+                $(
+                    if self.$field != base.$field {
+                        target.$field = self.$field.clone();
+                    }
+                )*
+            }
+        }
+    };
+}
+
+global_config! {
+    theme: Theme,
+    /// `None` follows the system locale.
+    language: Option<Language>,
+    knobs: KnobSettings,
+    /// Draws the recent path on the XY pad.
+    xy_pad_trail: bool,
+    hide_tooltips: bool,
+}
+
+impl GlobalConfig {
+    fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(CONFIG_DIR_NAME).join(CONFIG_FILE_NAME))
+    }
+
+    /// Reads the config file, falling back to the defaults if it doesn't exist or can't be parsed.
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|err| {
+                nih_log!("Ignoring invalid config file {}: {err}", path.display());
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Writes the settings that differ from `base`, the config as this instance last read or
+    /// wrote it, on top of the file's current contents. That way another instance's changes made
+    /// in the meantime aren't reverted. Failures are only logged, since there is nothing the user
+    /// could do about them from within the plugin.
+    pub fn save_changes(&self, base: &GlobalConfig) {
+        let Some(path) = Self::path() else {
+            return;
+        };
+
+        let mut merged = Self::load();
+        self.merge_changes(base, &mut merged);
+
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| {
+                let contents =
+                    serde_json::to_string_pretty(&merged).map_err(std::io::Error::other)?;
+                fs::write(&path, contents)
+            });
+        if let Err(err) = result {
+            nih_log!("Failed to write config file {}: {err}", path.display());
+        }
+    }
+}
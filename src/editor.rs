@@ -1,211 +1,1676 @@
 use atomic_float::AtomicF32;
-use nih_plug::prelude::Editor;
-use nih_plug::util;
-use std::sync::Arc;
+use nih_plug::nih_log;
+use nih_plug::prelude::{Editor, GuiContext, Param, ParamPtr};
+use std::io;
 use std::sync::atomic::Ordering;
+use std::sync::{Arc, RwLock};
 use vizia_plug::vizia::prelude::*;
+use vizia_plug::widgets::RawParamEvent;
+use vizia_plug::widgets::util::ModifiersExt;
 use vizia_plug::{ViziaState, ViziaTheming, create_vizia_editor};
 
 use crate::DisperserParams;
-use crate::widgets::omg_peak_meter::OmgPeakMeter;
-use crate::widgets::params_knob::ParamKnob;
-use crate::widgets::waveform_view::WaveformView;
+use crate::ab::{AbSlot, AbSlots};
+use crate::analysis::allpass::ChainSettings;
+use crate::analysis::loudness::LoudnessReadings;
+use crate::analysis::peak::PeakMeterState;
+use crate::analysis::taps::SignalTaps;
+use crate::config::GlobalConfig;
+use crate::localize::{self, Language};
+use crate::midi::{MappableParam, MidiMapping};
+use crate::preset::{self, Preset, PresetId, PresetSource, PresetValues};
+use crate::theme::Theme;
+use crate::undo::{GestureId, UndoHistory};
+use crate::widgets::correlation_meter::CorrelationMeter;
+use crate::widgets::group_delay_view::GroupDelayView;
+use crate::widgets::impulse_response_view::ImpulseResponseView;
+use crate::widgets::loudness_readout::LoudnessReadout;
+use crate::widgets::omg_peak_meter::{OmgPeakMeter, OmgPeakMeterHandle, PeakReadout};
+use crate::widgets::params_knob::{
+    DragMode, KnobSettings, ParamKnob, ParamKnobHandle, ParamKnobMenuEvent, SENSITIVITY_RANGE,
+};
+use crate::widgets::phase_response_view::PhaseResponseView;
+use crate::widgets::pole_zero_plot::PoleZeroPlot;
+use crate::widgets::resize_corner::ResizeCorner;
+use crate::widgets::spectrogram_view::SpectrogramView;
+use crate::widgets::spectrum_view::SpectrumView;
+use crate::widgets::vectorscope::Vectorscope;
+use crate::widgets::waveform_view::{WaveformView, WaveformViewHandle};
+use crate::widgets::xy_pad::{XyPad, XyPadHandle};
 
 // pub const NOTO_SANS: &str = "Noto Sans";
 
+/// The integration times the stereo meters cycle through, in milliseconds.
+const STEREO_INTEGRATION_TIMES_MS: [f32; 4] = [50.0, 300.0, 1000.0, 3000.0];
+/// The oscilloscope timebases to cycle through, in milliseconds.
+const SCOPE_TIMEBASES_MS: [f32; 6] = [2.0, 5.0, 10.0, 20.0, 50.0, 100.0];
+/// How often the editor sends CC-driven values to the host and picks up learned mappings.
+const MIDI_SYNC_INTERVAL: Duration = Duration::from_millis(50);
+/// How long the settings have to stay unchanged before they're written to the config file, so a
+/// slider drag writes it once rather than on every step.
+const CONFIG_SAVE_DELAY: Duration = Duration::from_millis(500);
+/// The id of the preset name box, the undo shortcut is left to it while it has focus.
+const PRESET_NAME_ENTRY_ID: &str = "preset-name-entry";
+/// The editor window's logical size, before UI scaling.
+pub(crate) const DEFAULT_SIZE: (u32, u32) = (800, 500);
+/// The range the resize corner can change the window's logical size in.
+const MIN_SIZE: (u32, u32) = (640, 400);
+const MAX_SIZE: (u32, u32) = (2400, 1600);
+/// The UI scale presets offered in the info panel. These scale the whole editor, while the resize
+/// corner changes the window's size and lets the layout reflow.
+const UI_SCALES: [(f64, &str); 6] = [
+    (0.75, "75%"),
+    (1.0, "100%"),
+    (1.25, "125%"),
+    (1.5, "150%"),
+    (1.75, "175%"),
+    (2.0, "200%"),
+];
+
+/// The views that can be shown in the spectrum panel. The selected tab is persisted in the plugin
+/// state by its index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum VisualizerTab {
+    Waveform,
+    Spectrum,
+    Spectrogram,
+    GroupDelay,
+    ImpulseResponse,
+    Phase,
+    PoleZero,
+}
+
+impl VisualizerTab {
+    const ALL: [VisualizerTab; 7] = [
+        VisualizerTab::Waveform,
+        VisualizerTab::Spectrum,
+        VisualizerTab::Spectrogram,
+        VisualizerTab::GroupDelay,
+        VisualizerTab::ImpulseResponse,
+        VisualizerTab::Phase,
+        VisualizerTab::PoleZero,
+    ];
+
+    /// Falls back to the waveform for indices from newer versions of the plugin.
+    pub(crate) fn from_index(index: u8) -> Self {
+        Self::ALL
+            .get(index as usize)
+            .copied()
+            .unwrap_or(VisualizerTab::Waveform)
+    }
+
+    fn localize_key(&self) -> &'static str {
+        match self {
+            VisualizerTab::Waveform => "tab-waveform",
+            VisualizerTab::Spectrum => "tab-spectrum",
+            VisualizerTab::Spectrogram => "tab-spectrogram",
+            VisualizerTab::GroupDelay => "tab-group-delay",
+            VisualizerTab::ImpulseResponse => "tab-impulse-response",
+            VisualizerTab::Phase => "tab-phase",
+            VisualizerTab::PoleZero => "tab-pole-zero",
+        }
+    }
+
+    /// The Fluent message id of the tab's tooltip, explaining what the view shows.
+    fn tooltip_key(&self) -> &'static str {
+        match self {
+            VisualizerTab::Waveform => "tooltip-waveform",
+            VisualizerTab::Spectrum => "tooltip-spectrum",
+            VisualizerTab::Spectrogram => "tooltip-spectrogram",
+            VisualizerTab::GroupDelay => "tooltip-group-delay",
+            VisualizerTab::ImpulseResponse => "tooltip-impulse-response",
+            VisualizerTab::Phase => "tooltip-phase",
+            VisualizerTab::PoleZero => "tooltip-pole-zero",
+        }
+    }
+
+    /// Whether the view reads the pre tap. The audio thread only fills that one while such a view
+    /// is shown, the post tap also feeds the meters in the top bar.
+    pub(crate) fn uses_pre_tap(&self) -> bool {
+        matches!(
+            self,
+            VisualizerTab::Waveform
+                | VisualizerTab::Spectrum
+                | VisualizerTab::Spectrogram
+                | VisualizerTab::Phase
+        )
+    }
+}
+
 #[derive(Lens)]
 struct Data {
     params: Arc<DisperserParams>,
+    gui_context: Arc<dyn GuiContext>,
     pre_signal: Arc<AtomicF32>,
     post_signal: Arc<AtomicF32>,
+    taps: Arc<SignalTaps>,
+    loudness: Arc<LoudnessReadings>,
+    output_peaks: Arc<PeakMeterState>,
     is_show_info_panel: bool,
+    is_show_settings_panel: bool,
+    is_spectrogram_split: bool,
+    stereo_integration_ms: f32,
+    is_scope_enabled: bool,
+    is_scope_frozen: bool,
+    scope_timebase_ms: f32,
+    visualizer_tab: VisualizerTab,
+    /// Shared by all instances, see [`GlobalConfig`].
+    config: GlobalConfig,
+    /// The config as last read from or written to the file, only the settings that differ from
+    /// this are written so other instances' changes survive.
+    saved_config: GlobalConfig,
+    /// When the settings were last changed, `None` once they've been written.
+    config_changed_at: Option<Instant>,
+    theme: Theme,
+    language: Option<Language>,
+    /// Mirrors the MIDI learn state and the mapping of every [`MappableParam`], these are
+    /// refreshed from the plugin's [`MidiMappings`](crate::midi::MidiMappings) periodically.
+    midi_learning: Option<MappableParam>,
+    midi_mappings: Vec<Option<MidiMapping>>,
+    /// The factory bank followed by the user's presets.
+    presets: Vec<Preset>,
+    /// Mirrors the preset remembered in the plugin state.
+    current_preset: Option<PresetId>,
+    is_show_preset_list: bool,
+    /// Set while the preset name box is shown in the top bar.
+    preset_name_entry: Option<PresetNameEntry>,
+    /// Set when the name entered belongs to another preset already.
+    is_preset_name_taken: bool,
+    /// Mirrors the A/B slots in the plugin state.
+    ab_slots: AbSlots,
+    undo_history: UndoHistory,
+    /// The undo group of the A/B morph slider while it's being dragged.
+    ab_morph_gesture: Option<GestureId>,
+}
+
+/// What the name typed into the preset bar is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub enum PresetNameEntry {
+    SaveAs,
+    Rename,
+}
+
+impl Data {
+    /// The settings the analysis views use to model the allpass cascade.
+    fn chain_settings(&self) -> ChainSettings {
+        ChainSettings {
+            frequency: self.params.frequency.value(),
+            spread: self.params.spread.value(),
+            amount: self.params.amount.value().max(0) as usize,
+            sample_rate: self.taps.sample_rate(),
+        }
+    }
+
+    fn update_knob_settings(&mut self, f: impl FnOnce(&mut KnobSettings)) {
+        f(&mut self.config.knobs);
+        self.config_changed();
+    }
+
+    /// Schedules the config to be written once the settings have settled.
+    fn config_changed(&mut self) {
+        self.config_changed_at = Some(Instant::now());
+    }
+
+    fn save_config(&mut self) {
+        if self.config_changed_at.take().is_some() {
+            self.config.save_changes(&self.saved_config);
+            self.saved_config = self.config.clone();
+        }
+    }
+
+    fn refresh_midi_state(&mut self) {
+        let mappings = &self.params.midi_mappings;
+        self.midi_learning = mappings.learning();
+        self.midi_mappings = MappableParam::ALL
+            .into_iter()
+            .map(|param| mappings.mapping(param))
+            .collect();
+    }
+
+    fn current_preset_index(&self) -> Option<usize> {
+        let current = self.current_preset.as_ref()?;
+        self.presets
+            .iter()
+            .position(|preset| preset.id() == *current)
+    }
+
+    /// The name of the current preset, marked when the parameters no longer match it.
+    fn preset_label(&self) -> Option<String> {
+        let preset = &self.presets[self.current_preset_index()?];
+        if preset
+            .values
+            .matches(&PresetValues::from_params(&self.params), &self.params)
+        {
+            Some(preset.name.clone())
+        } else {
+            Some(format!("{} *", preset.name))
+        }
+    }
+
+    fn is_user_preset_selected(&self) -> bool {
+        self.current_preset
+            .as_ref()
+            .is_some_and(|id| id.source == PresetSource::User)
+    }
+
+    fn set_current_preset(&mut self, id: Option<PresetId>) {
+        if let Ok(mut preset) = self.params.preset.write() {
+            *preset = id.clone();
+        }
+        self.current_preset = id;
+    }
+
+    fn load_preset(&mut self, cx: &mut EventContext, index: usize) {
+        let Some(preset) = self.presets.get(index) else {
+            return;
+        };
+        set_parameters(cx, preset.values.normalized_values(&self.params));
+        self.set_current_preset(Some(preset.id()));
+    }
+
+    /// Changes the A/B slots here and in the plugin state, then sets the parameters to the
+    /// slots' mix.
+    fn update_ab_slots(&mut self, cx: &mut EventContext, f: impl FnOnce(&mut AbSlots)) {
+        let mut slots = self.ab_slots.clone();
+        slots.store_active(&self.params);
+        f(&mut slots);
+
+        if let Some(values) = slots.morphed_values(&self.params) {
+            set_parameters(cx, values);
+        }
+        if let Ok(mut ab_slots) = self.params.ab_slots.write() {
+            *ab_slots = slots.clone();
+        }
+        self.ab_slots = slots;
+    }
+
+    /// Loads the preset `offset` places from the current one, wrapping around the list.
+    fn step_preset(&mut self, cx: &mut EventContext, offset: isize) {
+        if self.presets.is_empty() {
+            return;
+        }
+        let index = match self.current_preset_index() {
+            Some(index) => {
+                (index as isize + offset).rem_euclid(self.presets.len() as isize) as usize
+            }
+            None => 0,
+        };
+        self.load_preset(cx, index);
+    }
+
+    /// Saves the current parameter values to the selected user preset.
+    fn save_user_preset(&mut self, index: usize) {
+        let preset = Preset {
+            values: PresetValues::from_params(&self.params),
+            ..self.presets[index].clone()
+        };
+        match preset::update_user_preset(&preset) {
+            Ok(()) => self.presets[index] = preset,
+            Err(err) => nih_log!("Failed to save preset {}: {err}", preset.name),
+        }
+    }
+
+    /// Saves the current parameter values as a new user preset, or renames the selected one.
+    /// Existing presets are never replaced, a taken name leaves the entry open for another one.
+    fn submit_preset_name(&mut self, entry: PresetNameEntry, name: &str) {
+        let selected_path = self
+            .current_preset_index()
+            .and_then(|index| self.presets[index].path.clone());
+        let result = match entry {
+            PresetNameEntry::SaveAs => preset::create_user_preset(
+                &self.presets,
+                name,
+                PresetValues::from_params(&self.params),
+            ),
+            PresetNameEntry::Rename => match self.current_preset_index() {
+                Some(index) => {
+                    preset::rename_user_preset(&self.presets, &self.presets[index], name)
+                }
+                None => return,
+            },
+        };
+
+        // Even a failed write or rename may have changed the files, so the list is always reloaded
+        self.presets = preset::load_all();
+        match result {
+            Ok(preset) => {
+                self.preset_name_entry = None;
+                self.set_current_preset(Some(preset.id()));
+            }
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                self.is_preset_name_taken = true;
+            }
+            Err(err) => {
+                self.preset_name_entry = None;
+                nih_log!("Failed to save preset {}: {err}", name.trim());
+
+                // A rename that failed halfway leaves the file with the new name inside
+                let selected = self
+                    .presets
+                    .iter()
+                    .find(|preset| preset.path.is_some() && preset.path == selected_path);
+                if let Some(id) = selected.map(Preset::id) {
+                    self.set_current_preset(Some(id));
+                }
+            }
+        }
+    }
+
+    fn update_midi_mapping(&mut self, param: MappableParam, f: impl FnOnce(&mut MidiMapping)) {
+        if let Some(mut mapping) = self.params.midi_mappings.mapping(param) {
+            f(&mut mapping);
+            self.params.midi_mappings.update(mapping);
+        }
+        self.refresh_midi_state();
+    }
+}
+
+impl Drop for Data {
+    fn drop(&mut self) {
+        // Closing the editor shouldn't lose changes still waiting for the timer
+        self.save_config();
+    }
 }
 
 impl Model for Data {
-    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
         event.map(|main_view_event, _meta| match main_view_event {
             MainViewEvent::ToggleInfoPanel => {
                 self.is_show_info_panel = !self.is_show_info_panel;
             }
+            MainViewEvent::ToggleSettingsPanel => {
+                self.is_show_settings_panel = !self.is_show_settings_panel;
+            }
+            MainViewEvent::ResetPeakHold => {
+                self.output_peaks.reset_max_peaks();
+            }
+            MainViewEvent::SetPeakHoldMs(hold_ms) => {
+                self.params.peak_hold_ms.store(*hold_ms, Ordering::Relaxed);
+            }
+            MainViewEvent::SetPeakDecayMs(decay_ms) => {
+                self.params
+                    .peak_decay_ms
+                    .store(*decay_ms, Ordering::Relaxed);
+            }
+            MainViewEvent::ToggleSpectrogramSplit => {
+                self.is_spectrogram_split = !self.is_spectrogram_split;
+            }
+            MainViewEvent::CycleStereoIntegration => {
+                let current = STEREO_INTEGRATION_TIMES_MS
+                    .iter()
+                    .position(|&ms| ms == self.stereo_integration_ms)
+                    .unwrap_or(0);
+                self.stereo_integration_ms =
+                    STEREO_INTEGRATION_TIMES_MS[(current + 1) % STEREO_INTEGRATION_TIMES_MS.len()];
+            }
+            MainViewEvent::ToggleScope => {
+                self.is_scope_enabled = !self.is_scope_enabled;
+                self.is_scope_frozen = false;
+            }
+            MainViewEvent::ToggleScopeFrozen => {
+                self.is_scope_frozen = !self.is_scope_frozen;
+            }
+            MainViewEvent::CycleScopeTimebase => {
+                let current = SCOPE_TIMEBASES_MS
+                    .iter()
+                    .position(|&ms| ms == self.scope_timebase_ms)
+                    .unwrap_or(0);
+                self.scope_timebase_ms =
+                    SCOPE_TIMEBASES_MS[(current + 1) % SCOPE_TIMEBASES_MS.len()];
+            }
+            MainViewEvent::SelectTab(tab) => {
+                self.visualizer_tab = *tab;
+                self.params
+                    .visualizer_tab
+                    .store(*tab as u8, Ordering::Relaxed);
+            }
+            MainViewEvent::SetTheme(theme) => {
+                self.theme = *theme;
+                self.config.theme = *theme;
+                self.config_changed();
+            }
+            MainViewEvent::SetLanguage(language) => {
+                self.language = *language;
+                self.config.language = *language;
+                self.config_changed();
+                cx.emit(EnvironmentEvent::SetLocale(
+                    Language::resolve(*language).langid(),
+                ));
+            }
+            MainViewEvent::SetUiScale(scale) => {
+                // `ViziaState` picks this up and stores it with the rest of the plugin state
+                cx.set_user_scale_factor(*scale);
+            }
+            MainViewEvent::ResizeWindow(width, height) => {
+                let size = (
+                    (*width).clamp(MIN_SIZE.0, MAX_SIZE.0),
+                    (*height).clamp(MIN_SIZE.1, MAX_SIZE.1),
+                );
+                let Ok(mut editor_size) = self.params.editor_size.write() else {
+                    return;
+                };
+                if *editor_size == size {
+                    return;
+                }
+
+                // The host asks the editor for its size, so it has to be stored first. Hosts that
+                // refuse the new size keep the old one.
+                let previous_size = std::mem::replace(&mut *editor_size, size);
+                drop(editor_size);
+                if self.gui_context.request_resize() {
+                    cx.emit(WindowEvent::SetSize(WindowSize::new(size.0, size.1)));
+                } else if let Ok(mut editor_size) = self.params.editor_size.write() {
+                    *editor_size = previous_size;
+                }
+            }
+            MainViewEvent::SetDragMode(drag_mode) => {
+                self.update_knob_settings(|settings| settings.drag_mode = *drag_mode);
+            }
+            MainViewEvent::SetDragSensitivity(sensitivity) => {
+                self.update_knob_settings(|settings| settings.drag_sensitivity = *sensitivity);
+            }
+            MainViewEvent::SetWheelSensitivity(sensitivity) => {
+                self.update_knob_settings(|settings| settings.wheel_sensitivity = *sensitivity);
+            }
+            MainViewEvent::ToggleDragAcceleration => {
+                self.update_knob_settings(|settings| {
+                    settings.acceleration = !settings.acceleration;
+                });
+            }
+            MainViewEvent::ToggleXyPadTrail => {
+                self.config.xy_pad_trail = !self.config.xy_pad_trail;
+                self.config_changed();
+            }
+            MainViewEvent::ToggleTooltips => {
+                self.config.hide_tooltips = !self.config.hide_tooltips;
+                self.config_changed();
+            }
+            MainViewEvent::SaveConfigIfSettled => {
+                if self
+                    .config_changed_at
+                    .is_some_and(|changed_at| changed_at.elapsed() >= CONFIG_SAVE_DELAY)
+                {
+                    self.save_config();
+                }
+            }
+            MainViewEvent::SyncMidi => {
+                // CCs only change the DSP directly, this lets the host and the knobs follow them
+                for param in MappableParam::ALL {
+                    let mappings = &self.params.midi_mappings;
+                    let Some(pending) = mappings.take_pending(param) else {
+                        continue;
+                    };
+                    // The host would never echo a value it already has
+                    let value = pending.normalized_value;
+                    if self.params.round_normalized(param, value)
+                        == self.params.unmodulated_normalized_value(param)
+                    {
+                        continue;
+                    }
+
+                    mappings.mark_sent(param, pending);
+                    self.undo_history.skip_gesture(param);
+                    let param_ptr = self.params.param_ptr(param);
+                    cx.emit(RawParamEvent::BeginSetParameter(param_ptr));
+                    cx.emit(RawParamEvent::SetParameterNormalized(param_ptr, value));
+                    cx.emit(RawParamEvent::EndSetParameter(param_ptr));
+                }
+                self.refresh_midi_state();
+            }
+            MainViewEvent::SetMidiMin(param, min) => {
+                self.update_midi_mapping(*param, |mapping| mapping.min = *min);
+            }
+            MainViewEvent::SetMidiMax(param, max) => {
+                self.update_midi_mapping(*param, |mapping| mapping.max = *max);
+            }
+            MainViewEvent::CycleMidiCurve(param) => {
+                self.update_midi_mapping(*param, |mapping| mapping.curve = mapping.curve.next());
+            }
+            MainViewEvent::ClearMidiMapping(param) => {
+                self.params.midi_mappings.unmap(*param);
+                self.refresh_midi_state();
+            }
+            MainViewEvent::TogglePresetList => {
+                self.is_show_preset_list = !self.is_show_preset_list;
+            }
+            MainViewEvent::LoadPreset(index) => {
+                self.load_preset(cx, *index);
+                self.is_show_preset_list = false;
+            }
+            MainViewEvent::StepPreset(offset) => {
+                self.step_preset(cx, *offset);
+            }
+            MainViewEvent::SavePreset => {
+                // Factory presets can't be overwritten, those get saved under a new name instead
+                match self.current_preset_index() {
+                    Some(index) if self.presets[index].source == PresetSource::User => {
+                        self.save_user_preset(index)
+                    }
+                    _ => {
+                        self.preset_name_entry = Some(PresetNameEntry::SaveAs);
+                        self.is_preset_name_taken = false;
+                    }
+                }
+            }
+            MainViewEvent::StartPresetNameEntry(entry) => {
+                self.preset_name_entry = Some(*entry);
+                self.is_preset_name_taken = false;
+            }
+            MainViewEvent::SubmitPresetName(name) => match self.preset_name_entry {
+                Some(entry) if !name.trim().is_empty() => self.submit_preset_name(entry, name),
+                _ => self.preset_name_entry = None,
+            },
+            MainViewEvent::CancelPresetNameEntry => {
+                self.preset_name_entry = None;
+                self.is_preset_name_taken = false;
+            }
+            MainViewEvent::DeletePreset => {
+                let Some(preset) = self.current_preset_index().map(|i| self.presets[i].clone())
+                else {
+                    return;
+                };
+                if preset.source == PresetSource::User {
+                    match preset::delete_user_preset(&preset) {
+                        Ok(()) => {
+                            self.presets = preset::load_all();
+                            self.set_current_preset(None);
+                        }
+                        Err(err) => nih_log!("Failed to delete preset {}: {err}", preset.name),
+                    }
+                }
+            }
+            MainViewEvent::SelectAbSlot(slot) => {
+                let slot = *slot;
+                // An empty slot starts out as a copy of the current parameters
+                let current = PresetValues::from_params(&self.params);
+                self.update_ab_slots(cx, |slots| {
+                    if slots.slot(slot).is_none() {
+                        slots.set_slot(slot, current);
+                    }
+                    slots.active = slot;
+                    slots.morph = slot.morph_position();
+                });
+            }
+            MainViewEvent::CopyAbSlot(from) => {
+                let from = *from;
+                self.update_ab_slots(cx, |slots| {
+                    if let Some(values) = slots.slot(from) {
+                        slots.set_slot(from.other(), values);
+                    }
+                });
+            }
+            MainViewEvent::BeginAbMorph => {
+                if self.ab_morph_gesture.is_none() {
+                    self.ab_morph_gesture = Some(self.undo_history.begin_group());
+                }
+            }
+            MainViewEvent::EndAbMorph => {
+                if let Some(id) = self.ab_morph_gesture.take() {
+                    self.undo_history.end_group(id);
+                }
+            }
+            MainViewEvent::SetAbMorph(morph) => {
+                let morph = *morph;
+                // There's nothing to morph between until both slots are filled
+                self.update_ab_slots(cx, |slots| {
+                    if slots.a.is_some() && slots.b.is_some() {
+                        slots.morph = morph;
+                    }
+                });
+            }
             MainViewEvent::OpenUrl(url) => {
                 if webbrowser::open(&url).is_err() {
                     println!("Failed to open URL: {}", url);
                 }
             }
         });
+
+        event.map(|knob_menu_event, _meta| match knob_menu_event {
+            ParamKnobMenuEvent::LearnMidiCc(param_ptr) => {
+                if let Some(param) = self.params.param_for_ptr(*param_ptr) {
+                    // Learning the same parameter again cancels it
+                    let target = (self.midi_learning != Some(param)).then_some(param);
+                    self.params.midi_mappings.learn(target);
+                    self.refresh_midi_state();
+                }
+            }
+        });
+
+        // The gestures on their way to the host. Automation doesn't go through here, so it isn't
+        // recorded.
+        event.map(|param_event, _meta| match param_event {
+            RawParamEvent::BeginSetParameter(param_ptr) => {
+                if let Some(param) = self.params.param_for_ptr(*param_ptr) {
+                    let value = self.params.unmodulated_normalized_value(param);
+                    self.undo_history.begin(param, value);
+                }
+            }
+            RawParamEvent::SetParameterNormalized(param_ptr, value) => {
+                if let Some(param) = self.params.param_for_ptr(*param_ptr) {
+                    self.undo_history.set(param, *value);
+                }
+            }
+            RawParamEvent::EndSetParameter(param_ptr) => {
+                if let Some(param) = self.params.param_for_ptr(*param_ptr) {
+                    self.undo_history.end(param);
+                }
+            }
+            _ => {}
+        });
+
+        event.map(|window_event, meta| {
+            if let WindowEvent::KeyDown(Code::KeyZ, _) = window_event {
+                // Text inputs keep the shortcut to themselves
+                let is_editing_text = cx
+                    .resolve_entity_identifier(PRESET_NAME_ENTRY_ID)
+                    .is_some_and(|entry| entry == cx.focused());
+                if cx.modifiers().command() && !is_editing_text {
+                    let values = if cx.modifiers().shift() {
+                        self.undo_history.redo()
+                    } else {
+                        self.undo_history.undo()
+                    };
+                    if let Some(values) = values {
+                        set_parameters(
+                            cx,
+                            values
+                                .into_iter()
+                                .map(|(param, value)| (self.params.param_ptr(param), value)),
+                        );
+                    }
+                    meta.consume();
+                }
+            }
+        });
     }
 }
 
 pub enum MainViewEvent {
     ToggleInfoPanel,
+    ToggleSettingsPanel,
+    ResetPeakHold,
+    SetPeakHoldMs(f32),
+    SetPeakDecayMs(f32),
+    ToggleSpectrogramSplit,
+    CycleStereoIntegration,
+    ToggleScope,
+    ToggleScopeFrozen,
+    CycleScopeTimebase,
+    SelectTab(VisualizerTab),
+    SetTheme(Theme),
+    SetLanguage(Option<Language>),
+    SetUiScale(f64),
+    /// Sets the window's logical size, within [`MIN_SIZE`] and [`MAX_SIZE`].
+    ResizeWindow(u32, u32),
+    SetDragMode(DragMode),
+    SetDragSensitivity(f32),
+    SetWheelSensitivity(f32),
+    ToggleDragAcceleration,
+    ToggleXyPadTrail,
+    ToggleTooltips,
+    /// Writes pending settings changes once they're [`CONFIG_SAVE_DELAY`] old.
+    SaveConfigIfSettled,
+    SyncMidi,
+    SetMidiMin(MappableParam, f32),
+    SetMidiMax(MappableParam, f32),
+    CycleMidiCurve(MappableParam),
+    ClearMidiMapping(MappableParam),
+    TogglePresetList,
+    LoadPreset(usize),
+    /// Loads the next preset, or the previous one for a negative offset.
+    StepPreset(isize),
+    /// Overwrites the current user preset, or asks for a name otherwise.
+    SavePreset,
+    StartPresetNameEntry(PresetNameEntry),
+    SubmitPresetName(String),
+    CancelPresetNameEntry,
+    DeletePreset,
+    /// Switches the parameters to an A/B slot.
+    SelectAbSlot(AbSlot),
+    /// Copies a slot over the other one.
+    CopyAbSlot(AbSlot),
+    /// The morph slider was grabbed, the changes until it's released are undone as one.
+    BeginAbMorph,
+    EndAbMorph,
+    SetAbMorph(f32),
     OpenUrl(String),
 }
 
-pub(crate) fn default_state() -> Arc<ViziaState> {
-    ViziaState::new(|| (800, 500))
+/// The oscilloscope toggle, and its timebase and freeze buttons while it is enabled.
+fn build_scope_controls(cx: &mut Context) {
+    Button::new(cx, |cx| Label::new(cx, Localized::new("scope")))
+        .on_press(|ex| {
+            ex.emit(MainViewEvent::ToggleScope);
+        })
+        .checked(Data::is_scope_enabled)
+        .class("top-bar-btn");
+
+    Binding::new(cx, Data::is_scope_enabled, |cx, enabled| {
+        if enabled.get(cx) {
+            Button::new(cx, |cx| {
+                Label::new(cx, Data::scope_timebase_ms.map(|ms| format!("{ms:.0}MS")))
+            })
+            .on_press(|ex| {
+                ex.emit(MainViewEvent::CycleScopeTimebase);
+            })
+            .class("top-bar-btn");
+
+            Button::new(cx, |cx| Label::new(cx, Localized::new("freeze")))
+                .on_press(|ex| {
+                    ex.emit(MainViewEvent::ToggleScopeFrozen);
+                })
+                .checked(Data::is_scope_frozen)
+                .class("top-bar-btn");
+        }
+    });
+}
+
+/// The drag mode, sensitivity and acceleration rows of the settings panel.
+fn build_knob_settings(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        Label::new(cx, Localized::new("drag-mode")).class("settings-label");
+        for drag_mode in DragMode::ALL {
+            Button::new(cx, move |cx| {
+                Label::new(cx, Localized::new(drag_mode.localize_key()))
+            })
+            .on_press(move |ex| ex.emit(MainViewEvent::SetDragMode(drag_mode)))
+            .checked(Data::config.map(move |config| config.knobs.drag_mode == drag_mode))
+            .class("choice-btn");
+        }
+        Button::new(cx, |cx| Label::new(cx, Localized::new("drag-acceleration")))
+            .on_press(|ex| ex.emit(MainViewEvent::ToggleDragAcceleration))
+            .checked(Data::config.map(|config| config.knobs.acceleration))
+            .class("choice-btn");
+    })
+    .class("settings-row");
+
+    HStack::new(cx, |cx| {
+        Label::new(cx, Localized::new("drag-sensitivity")).class("settings-label");
+        Slider::new(cx, Data::config.map(|config| config.knobs.drag_sensitivity))
+            .range(SENSITIVITY_RANGE)
+            .step(0.05)
+            .on_change(|ex, sensitivity| ex.emit(MainViewEvent::SetDragSensitivity(sensitivity)))
+            .class("settings-slider");
+        Label::new(
+            cx,
+            Data::config.map(|config| format!("{:.2}x", config.knobs.drag_sensitivity)),
+        )
+        .class("settings-value");
+    })
+    .class("settings-row");
+
+    HStack::new(cx, |cx| {
+        Label::new(cx, Localized::new("wheel-sensitivity")).class("settings-label");
+        Slider::new(
+            cx,
+            Data::config.map(|config| config.knobs.wheel_sensitivity),
+        )
+        .range(SENSITIVITY_RANGE)
+        .step(0.05)
+        .on_change(|ex, sensitivity| ex.emit(MainViewEvent::SetWheelSensitivity(sensitivity)))
+        .class("settings-slider");
+        Label::new(
+            cx,
+            Data::config.map(|config| format!("{:.2}x", config.knobs.wheel_sensitivity)),
+        )
+        .class("settings-value");
+    })
+    .class("settings-row");
+
+    HStack::new(cx, |cx| {
+        Label::new(cx, Localized::new("xy-pad")).class("settings-label");
+        Button::new(cx, |cx| Label::new(cx, Localized::new("xy-pad-trail")))
+            .on_press(|ex| ex.emit(MainViewEvent::ToggleXyPadTrail))
+            .checked(Data::config.map(|config| config.xy_pad_trail))
+            .class("choice-btn");
+    })
+    .class("settings-row");
+}
+
+/// Sets parameters from the editor. The gestures overlap, so they form a single undo step.
+fn set_parameters(cx: &mut EventContext, values: impl IntoIterator<Item = (ParamPtr, f32)>) {
+    let values: Vec<_> = values.into_iter().collect();
+    for (param_ptr, _) in &values {
+        cx.emit(RawParamEvent::BeginSetParameter(*param_ptr));
+    }
+    for (param_ptr, value) in &values {
+        cx.emit(RawParamEvent::SetParameterNormalized(*param_ptr, *value));
+    }
+    for (param_ptr, _) in &values {
+        cx.emit(RawParamEvent::EndSetParameter(*param_ptr));
+    }
+}
+
+/// The A/B slot buttons, the copy buttons and the morph slider.
+fn build_ab_controls(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        HStack::new(cx, |cx| {
+            for slot in [AbSlot::A, AbSlot::B] {
+                let name = match slot {
+                    AbSlot::A => "A",
+                    AbSlot::B => "B",
+                };
+                Button::new(cx, move |cx| Label::new(cx, name))
+                    .on_press(move |ex| ex.emit(MainViewEvent::SelectAbSlot(slot)))
+                    .checked(Data::ab_slots.map(move |slots| slots.active == slot))
+                    .class("choice-btn");
+            }
+            Button::new(cx, |cx| Label::new(cx, "A→B"))
+                .on_press(|ex| ex.emit(MainViewEvent::CopyAbSlot(AbSlot::A)))
+                .class("choice-btn");
+            Button::new(cx, |cx| Label::new(cx, "B→A"))
+                .on_press(|ex| ex.emit(MainViewEvent::CopyAbSlot(AbSlot::B)))
+                .class("choice-btn");
+        })
+        .class("ab-controls__row");
+
+        HStack::new(cx, |cx| {
+            Label::new(cx, Localized::new("ab-morph")).class("ab-controls__label");
+            Slider::new(cx, Data::ab_slots.map(|slots| slots.morph))
+                .range(0.0..1.0)
+                .on_mouse_down(|ex, button| {
+                    if button == MouseButton::Left {
+                        ex.emit(MainViewEvent::BeginAbMorph);
+                    }
+                })
+                .on_mouse_up(|ex, button| {
+                    if button == MouseButton::Left {
+                        ex.emit(MainViewEvent::EndAbMorph);
+                    }
+                })
+                .on_change(|ex, morph| ex.emit(MainViewEvent::SetAbMorph(morph)))
+                .class("ab-controls__slider");
+        })
+        .class("ab-controls__row");
+    })
+    .class("ab-controls");
+}
+
+/// The preset selector in the top bar, with the buttons to manage the user's presets.
+fn build_preset_bar(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        Button::new(cx, |cx| Label::new(cx, "<"))
+            .on_press(|ex| ex.emit(MainViewEvent::StepPreset(-1)))
+            .class("top-bar-btn");
+
+        Binding::new(cx, Data::preset_name_entry, |cx, entry| {
+            if entry.get(cx).is_some() {
+                Textbox::new(
+                    cx,
+                    Data::current_preset
+                        .map(|id| id.as_ref().map_or(String::new(), |id| id.name.clone())),
+                )
+                .on_submit(|ex, name, success| {
+                    if success {
+                        ex.emit(MainViewEvent::SubmitPresetName(name));
+                    } else {
+                        ex.emit(MainViewEvent::CancelPresetNameEntry);
+                    }
+                })
+                .on_cancel(|ex| ex.emit(MainViewEvent::CancelPresetNameEntry))
+                .on_build(|ex| {
+                    ex.emit(TextEvent::StartEdit);
+                    ex.emit(TextEvent::SelectAll);
+                })
+                .toggle_class("invalid", Data::is_preset_name_taken)
+                .id(PRESET_NAME_ENTRY_ID)
+                .class("preset-bar__entry");
+                Label::new(cx, Localized::new("preset-name-taken"))
+                    .display(Data::is_preset_name_taken)
+                    .class("preset-bar__error");
+            } else {
+                Binding::new(
+                    cx,
+                    Data::root.map(|data| data.preset_label()),
+                    |cx, label| {
+                        let label = label.get(cx);
+                        Button::new(cx, move |cx| match &label {
+                            Some(label) => Label::new(cx, label.clone()),
+                            None => Label::new(cx, Localized::new("preset-none")),
+                        })
+                        .on_press(|ex| ex.emit(MainViewEvent::TogglePresetList))
+                        .checked(Data::is_show_preset_list)
+                        .class("top-bar-btn")
+                        .class("preset-bar__name");
+                    },
+                );
+            }
+        });
+
+        Button::new(cx, |cx| Label::new(cx, ">"))
+            .on_press(|ex| ex.emit(MainViewEvent::StepPreset(1)))
+            .class("top-bar-btn");
+
+        let no_user_preset = Data::root.map(|data| !data.is_user_preset_selected());
+        Button::new(cx, |cx| Label::new(cx, Localized::new("preset-save")))
+            .on_press(|ex| ex.emit(MainViewEvent::SavePreset))
+            .class("top-bar-btn");
+        Button::new(cx, |cx| Label::new(cx, Localized::new("preset-save-as")))
+            .on_press(|ex| ex.emit(MainViewEvent::StartPresetNameEntry(PresetNameEntry::SaveAs)))
+            .class("top-bar-btn");
+        Button::new(cx, |cx| Label::new(cx, Localized::new("preset-rename")))
+            .on_press(|ex| ex.emit(MainViewEvent::StartPresetNameEntry(PresetNameEntry::Rename)))
+            .disabled(no_user_preset)
+            .class("top-bar-btn");
+        Button::new(cx, |cx| Label::new(cx, Localized::new("preset-delete")))
+            .on_press(|ex| ex.emit(MainViewEvent::DeletePreset))
+            .disabled(no_user_preset)
+            .class("top-bar-btn");
+    })
+    .class("preset-bar");
+}
+
+/// The list that drops down from the preset name, factory presets first.
+fn build_preset_list(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        Binding::new(cx, Data::presets, |cx, presets| {
+            let presets = presets.get(cx);
+            for source in [PresetSource::Factory, PresetSource::User] {
+                let key = match source {
+                    PresetSource::Factory => "preset-factory",
+                    PresetSource::User => "preset-user",
+                };
+                Label::new(cx, Localized::new(key)).class("preset-list__head");
+                for (index, preset) in presets.iter().enumerate() {
+                    if preset.source != source {
+                        continue;
+                    }
+                    let id = preset.id();
+                    Button::new(cx, {
+                        let name = preset.name.clone();
+                        move |cx| Label::new(cx, name.clone())
+                    })
+                    .on_press(move |ex| ex.emit(MainViewEvent::LoadPreset(index)))
+                    .checked(Data::current_preset.map(move |current| current.as_ref() == Some(&id)))
+                    .class("preset-list__item");
+                }
+            }
+        });
+    })
+    .on_press(|_| {})
+    .class("preset-list");
+}
+
+/// A tooltip with a short explanation. The global toggle hides them through the `hide-tooltips`
+/// class on the root.
+fn build_tooltip(cx: &mut Context, text: Localized) -> Handle<'_, Tooltip> {
+    Tooltip::new(cx, move |cx| {
+        Label::new(cx, text.clone()).class("tooltip__text");
+    })
+}
+
+/// The parameter's current value with its unit, as shown on its knob.
+fn param_display<P: Param>(
+    param: impl Fn(&DisperserParams) -> &P + Copy + 'static,
+) -> impl Lens<Target = String> {
+    Data::params.map(move |params| {
+        let param = param(params);
+        param.normalized_value_to_string(param.unmodulated_normalized_value(), true)
+    })
+}
+
+fn midi_mapping(param: MappableParam) -> impl Lens<Target = Option<MidiMapping>> {
+    Data::midi_mappings.map(move |mappings| mappings.get(param as usize).copied().flatten())
+}
+
+/// A row in the settings panel with the CC, range and curve of a parameter's mapping.
+fn build_midi_mapping_row(cx: &mut Context, param: MappableParam) {
+    HStack::new(cx, |cx| {
+        Label::new(cx, Localized::new(param.localize_key())).class("settings-label");
+
+        // Rebuilt when the mapping is added, removed or changes its curve, but not while dragging
+        // the range sliders
+        Binding::new(
+            cx,
+            midi_mapping(param).map(|mapping| mapping.map(|m| m.curve)),
+            move |cx, curve| {
+                if let Some(curve) = curve.get(cx) {
+                    Label::new(
+                        cx,
+                        midi_mapping(param).map(|mapping| {
+                            mapping.map_or(String::new(), |m| format!("CC {}", m.cc))
+                        }),
+                    )
+                    .class("settings-value");
+                    Slider::new(
+                        cx,
+                        midi_mapping(param).map(|mapping| mapping.map_or(0.0, |m| m.min)),
+                    )
+                    .range(0.0..1.0)
+                    .on_change(move |ex, min| ex.emit(MainViewEvent::SetMidiMin(param, min)))
+                    .class("settings-slider");
+                    Slider::new(
+                        cx,
+                        midi_mapping(param).map(|mapping| mapping.map_or(1.0, |m| m.max)),
+                    )
+                    .range(0.0..1.0)
+                    .on_change(move |ex, max| ex.emit(MainViewEvent::SetMidiMax(param, max)))
+                    .class("settings-slider");
+                    Button::new(cx, move |cx| {
+                        Label::new(cx, Localized::new(curve.localize_key()))
+                    })
+                    .on_press(move |ex| ex.emit(MainViewEvent::CycleMidiCurve(param)))
+                    .class("choice-btn");
+                    Button::new(cx, |cx| Label::new(cx, Localized::new("midi-clear")))
+                        .on_press(move |ex| ex.emit(MainViewEvent::ClearMidiMapping(param)))
+                        .class("choice-btn");
+                } else {
+                    Binding::new(cx, Data::midi_learning, move |cx, learning| {
+                        let key = if learning.get(cx) == Some(param) {
+                            "midi-learning"
+                        } else {
+                            "midi-unmapped"
+                        };
+                        Label::new(cx, Localized::new(key)).class("settings-hint");
+                    });
+                }
+            },
+        );
+    })
+    .class("settings-row");
+}
+
+/// The window's size is whatever `size` holds, so the resize corner can change it at runtime.
+/// UI scaling is applied on top of that.
+pub(crate) fn default_state(size: Arc<RwLock<(u32, u32)>>) -> Arc<ViziaState> {
+    ViziaState::new(move || size.read().map_or(DEFAULT_SIZE, |size| *size))
 }
 
 pub(crate) fn create(
     params: Arc<DisperserParams>,
     pre_signal: Arc<AtomicF32>,
     post_signal: Arc<AtomicF32>,
+    taps: Arc<SignalTaps>,
+    loudness: Arc<LoudnessReadings>,
+    output_peaks: Arc<PeakMeterState>,
     editor_state: Arc<ViziaState>,
 ) -> Option<Box<dyn Editor>> {
-    create_vizia_editor(editor_state, ViziaTheming::Custom, move |cx, _| {
+    create_vizia_editor(editor_state, ViziaTheming::Custom, move |cx, context| {
         // assets::register_noto_sans_light(cx);
         // assets::register_noto_sans_thin(cx);
 
         cx.add_stylesheet(include_style!("src/style.css"))
             .expect("err when include style.css");
+        cx.add_stylesheet(include_style!("src/themes.css"))
+            .expect("err when include themes.css");
         cx.add_font_mem(include_bytes!("../assets/JetBrainsMono-Bold.ttf"));
+        localize::add_translations(cx);
+
+        let config = GlobalConfig::load();
+        let locale = Language::resolve(config.language).langid();
 
         Data {
             params: params.clone(),
+            gui_context: context,
             pre_signal: pre_signal.clone(),
             post_signal: post_signal.clone(),
+            taps: taps.clone(),
+            loudness: loudness.clone(),
+            output_peaks: output_peaks.clone(),
             is_show_info_panel: false,
+            is_show_settings_panel: false,
+            is_spectrogram_split: false,
+            stereo_integration_ms: STEREO_INTEGRATION_TIMES_MS[1],
+            is_scope_enabled: false,
+            is_scope_frozen: false,
+            scope_timebase_ms: SCOPE_TIMEBASES_MS[2],
+            visualizer_tab: VisualizerTab::from_index(
+                params.visualizer_tab.load(Ordering::Relaxed),
+            ),
+            theme: config.theme,
+            language: config.language,
+            saved_config: config.clone(),
+            config,
+            config_changed_at: None,
+            midi_learning: None,
+            midi_mappings: Vec::new(),
+            presets: preset::load_all(),
+            current_preset: params.preset.read().ok().and_then(|preset| preset.clone()),
+            is_show_preset_list: false,
+            preset_name_entry: None,
+            is_preset_name_taken: false,
+            ab_slots: params
+                .ab_slots
+                .read()
+                .map(|slots| slots.clone())
+                .unwrap_or_default(),
+            undo_history: UndoHistory::default(),
+            ab_morph_gesture: None,
         }
         .build(cx);
+        cx.emit(MainViewEvent::SyncMidi);
 
-        VStack::new(cx, |cx| {
-            VStack::new(cx, |cx| {
-                HStack::new(cx, |cx| {
-                    Label::new(cx, "IM_DISPERSER").class("top-bar-text");
+        let midi_sync_timer = cx.add_timer(MIDI_SYNC_INTERVAL, None, |cx, action| {
+            if let TimerAction::Tick(_) = action {
+                cx.emit(MainViewEvent::SyncMidi);
+            }
+        });
+        cx.start_timer(midi_sync_timer);
 
-                    HStack::new(cx, |_| {}).width(Stretch(1.0));
+        let config_save_timer = cx.add_timer(CONFIG_SAVE_DELAY / 2, None, |cx, action| {
+            if let TimerAction::Tick(_) = action {
+                cx.emit(MainViewEvent::SaveConfigIfSettled);
+            }
+        });
+        cx.start_timer(config_save_timer);
+
+        cx.emit(EnvironmentEvent::SetLocale(locale));
 
+        // Themes are applied as a class on this root, so the overlays pick them up as well
+        let mut root = ZStack::new(cx, |cx| {
+            VStack::new(cx, |cx| {
+                VStack::new(cx, |cx| {
                     HStack::new(cx, |cx| {
-                        Label::new(cx, "PROCESSING").class("top-bar-text");
+                        Label::new(cx, "IM_DISPERSER").class("top-bar-text");
 
-                        OmgPeakMeter::new(
-                            cx,
-                            Data::post_signal.map(|post_signal| {
-                                util::gain_to_db(post_signal.load(Ordering::Relaxed))
-                            }),
-                            Some(Duration::from_millis(600)),
-                        )
-                        .class("peak-meter");
-
-                        Button::new(cx, |cx| Label::new(cx, "?").alignment(Alignment::TopCenter))
+                        build_preset_bar(cx);
+
+                        HStack::new(cx, |_| {}).width(Stretch(1.0));
+
+                        HStack::new(cx, |cx| {
+                            Label::new(cx, Localized::new("processing")).class("top-bar-text");
+
+                            OmgPeakMeter::new(
+                                cx,
+                                [
+                                    Data::output_peaks.map(|peaks| peaks.level_db(0)),
+                                    Data::output_peaks.map(|peaks| peaks.level_db(1)),
+                                ],
+                                Some(Duration::from_millis(600)),
+                                Orientation::Horizontal,
+                            )
+                            .hold_time_ms(
+                                Data::params
+                                    .map(|params| params.peak_hold_ms.load(Ordering::Relaxed)),
+                            )
+                            .class("peak-meter")
+                            .tooltip(|cx| build_tooltip(cx, Localized::new("tooltip-peak-meter")));
+
+                            PeakReadout::new(
+                                cx,
+                                Data::output_peaks.map(|peaks| peaks.max_peak_db()),
+                                Data::output_peaks.map(|peaks| peaks.is_clipped()),
+                            )
+                            .on_press(|ex| {
+                                ex.emit(MainViewEvent::ResetPeakHold);
+                            })
+                            .class("peak-readout")
+                            .tooltip(|cx| {
+                                build_tooltip(cx, Localized::new("tooltip-peak-readout"))
+                            });
+
+                            Vectorscope::new(cx, Data::taps, Data::stereo_integration_ms)
+                                .class("vectorscope")
+                                .tooltip(|cx| {
+                                    build_tooltip(cx, Localized::new("tooltip-vectorscope"))
+                                });
+
+                            CorrelationMeter::new(cx, Data::taps, Data::stereo_integration_ms)
+                                .class("correlation-meter")
+                                .tooltip(|cx| {
+                                    build_tooltip(cx, Localized::new("tooltip-correlation"))
+                                });
+
+                            Button::new(cx, |cx| {
+                                Label::new(
+                                    cx,
+                                    Data::stereo_integration_ms.map(|ms| format!("{ms:.0}MS")),
+                                )
+                            })
+                            .on_press(|ex| {
+                                ex.emit(MainViewEvent::CycleStereoIntegration);
+                            })
+                            .class("top-bar-btn");
+
+                            Button::new(cx, |cx| Label::new(cx, Localized::new("settings-toggle")))
+                                .on_press(|ex| {
+                                    ex.emit(MainViewEvent::ToggleSettingsPanel);
+                                })
+                                .checked(Data::is_show_settings_panel)
+                                .class("top-bar-btn");
+
+                            Button::new(cx, |cx| {
+                                Label::new(cx, "?").alignment(Alignment::TopCenter)
+                            })
                             .on_press(|ex| {
                                 ex.emit(MainViewEvent::ToggleInfoPanel);
                             })
                             .class("info-btn");
+                        })
+                        .class("top-bar-right");
                     })
-                    .class("top-bar-right");
-                })
-                .class("top-bar");
+                    .class("top-bar");
 
-                VStack::new(cx, |cx| {
-                    WaveformView::new(
-                        cx,
-                        Data::pre_signal.map(|pre_signal| pre_signal.load(Ordering::Relaxed)),
-                        Data::post_signal.map(|post_signal| post_signal.load(Ordering::Relaxed)),
-                        512,
-                    )
-                    .class("waveform-view");
-                })
-                .height(Stretch(1.0));
-            })
-            .class("spectrum-panel");
+                    HStack::new(cx, |cx| {
+                        for tab in VisualizerTab::ALL {
+                            Button::new(cx, move |cx| {
+                                Label::new(cx, Localized::new(tab.localize_key()))
+                            })
+                            .on_press(move |ex| {
+                                ex.emit(MainViewEvent::SelectTab(tab));
+                            })
+                            .checked(Data::visualizer_tab.map(move |selected| *selected == tab))
+                            .class("top-bar-btn")
+                            .tooltip(move |cx| {
+                                build_tooltip(cx, Localized::new(tab.tooltip_key()))
+                            });
+                        }
 
-            VStack::new(cx, |cx| {
-                VStack::new(cx, |_cx| {})
-                    .width(Percentage(100.0))
-                    .class("shadow-bar");
+                        HStack::new(cx, |_| {}).width(Stretch(1.0));
 
-                HStack::new(cx, |cx| {
-                    VStack::new(cx, |cx| {
-                        Label::new(cx, "IM_DISPERSER")
-                            .font_size(24.0)
-                            .background_color(Color::rgb(18, 23, 19))
-                            .color(Color::rgb(243, 255, 244))
-                            .class("animated-label");
-                        Label::new(cx, "DSP CORE BY IAMMRDODIE")
-                            .font_size(12.0)
-                            .color(Color::rgb(18, 23, 19))
-                            .class("animated-label");
+                        // Controls that only apply to the selected view
+                        Binding::new(cx, Data::visualizer_tab, |cx, tab| {
+                            HStack::new(cx, |cx| match tab.get(cx) {
+                                VisualizerTab::Waveform => build_scope_controls(cx),
+                                VisualizerTab::Spectrogram => {
+                                    Button::new(cx, |cx| {
+                                        Label::new(cx, Localized::new("pre-post"))
+                                    })
+                                    .on_press(|ex| {
+                                        ex.emit(MainViewEvent::ToggleSpectrogramSplit);
+                                    })
+                                    .checked(Data::is_spectrogram_split)
+                                    .class("top-bar-btn");
+                                }
+                                _ => {}
+                            })
+                            .class("tab-strip__controls");
+                        });
                     })
-                    .gap(Pixels(4.0))
-                    .padding_left(Pixels(48.0))
-                    .alignment(Alignment::Left);
+                    .class("tab-strip");
 
-                    HStack::new(cx, |_| {}).width(Stretch(1.0));
+                    // Only the selected view is built, so hidden views don't analyse or draw anything
+                    Binding::new(cx, Data::visualizer_tab, |cx, tab| match tab.get(cx) {
+                        VisualizerTab::Waveform => {
+                            WaveformView::new(cx, 512)
+                                .trace(
+                                    "pre",
+                                    Data::pre_signal
+                                        .map(|pre_signal| pre_signal.load(Ordering::Relaxed)),
+                                )
+                                .trace(
+                                    "post",
+                                    Data::post_signal
+                                        .map(|post_signal| post_signal.load(Ordering::Relaxed)),
+                                )
+                                .oscilloscope(
+                                    Data::taps,
+                                    Data::is_scope_enabled,
+                                    Data::scope_timebase_ms,
+                                    Data::is_scope_frozen,
+                                )
+                                .class("waveform-view");
+                        }
+                        VisualizerTab::Spectrum => {
+                            SpectrumView::new(cx, Data::taps).class("spectrum-view");
+                        }
+                        VisualizerTab::Spectrogram => {
+                            SpectrogramView::new(cx, Data::taps, Data::is_spectrogram_split)
+                                .class("spectrogram-view");
+                        }
+                        VisualizerTab::GroupDelay => {
+                            GroupDelayView::new(cx, Data::root.map(|data| data.chain_settings()))
+                                .class("group-delay-view");
+                        }
+                        VisualizerTab::ImpulseResponse => {
+                            ImpulseResponseView::new(
+                                cx,
+                                Data::root.map(|data| data.chain_settings()),
+                            )
+                            .class("impulse-response-view");
+                        }
+                        VisualizerTab::Phase => {
+                            PhaseResponseView::new(
+                                cx,
+                                Data::taps,
+                                Data::root.map(|data| data.chain_settings()),
+                            )
+                            .class("phase-response-view");
+                        }
+                        VisualizerTab::PoleZero => {
+                            PoleZeroPlot::new(cx, Data::root.map(|data| data.chain_settings()))
+                                .class("pole-zero-plot");
+                        }
+                    });
+                })
+                .class("spectrum-panel");
+
+                VStack::new(cx, |cx| {
+                    VStack::new(cx, |_cx| {})
+                        .width(Percentage(100.0))
+                        .class("shadow-bar");
 
                     HStack::new(cx, |cx| {
                         VStack::new(cx, |cx| {
-                            ParamKnob::new(cx, Data::params, |params| &params.amount, true)
-                                .class("knob");
-                            Label::new(cx, "AMOUNT").class("params-label");
+                            Label::new(cx, "IM_DISPERSER")
+                                .class("title-label")
+                                .class("animated-label");
+                            Label::new(cx, Localized::new("subtitle"))
+                                .class("subtitle-label")
+                                .class("animated-label");
                         })
-                        .class("knob-cont");
+                        .gap(Pixels(4.0))
+                        .padding_left(Pixels(48.0))
+                        .alignment(Alignment::Left);
 
-                        VStack::new(cx, |cx| {
-                            ParamKnob::new(cx, Data::params, |params| &params.spread, true)
-                                .class("knob");
-                            Label::new(cx, "SPREAD").class("params-label");
-                        })
-                        // genshin impact is the worst game in the world
-                        .class("knob-cont");
+                        HStack::new(cx, |_| {}).width(Stretch(1.0));
 
-                        VStack::new(cx, |cx| {
-                            ParamKnob::new(cx, Data::params, |params| &params.frequency, true)
-                                .class("knob");
-                            Label::new(cx, "FREQUENCY").class("params-label");
+                        LoudnessReadout::new(cx, Data::loudness).class("loudness-readout");
+
+                        build_ab_controls(cx);
+
+                        HStack::new(cx, |_| {}).width(Stretch(1.0));
+
+                        HStack::new(cx, |cx| {
+                            // Frequency across and spread upwards, the way the effect is usually
+                            // swept
+                            XyPad::new(
+                                cx,
+                                Data::params,
+                                |params| &params.frequency,
+                                |params| &params.spread,
+                            )
+                            .trail(Data::config.map(|config| config.xy_pad_trail))
+                            .class("xy-pad")
+                            .tooltip(|cx| build_tooltip(cx, Localized::new("tooltip-xy-pad")));
+
+                            VStack::new(cx, |cx| {
+                                ParamKnob::new(cx, Data::params, |params| &params.amount, true)
+                                    .settings(Data::config.map(|config| config.knobs))
+                                    .default_detent(Some("detent-default"))
+                                    .class("knob")
+                                    .toggle_class(
+                                        "midi-learning",
+                                        Data::midi_learning.map(|learning| {
+                                            *learning == Some(MappableParam::Amount)
+                                        }),
+                                    )
+                                    .tooltip(|cx| {
+                                        build_tooltip(
+                                            cx,
+                                            Localized::new("tooltip-amount").arg(
+                                                "value",
+                                                param_display(|params| &params.amount),
+                                            ),
+                                        )
+                                    });
+                                Label::new(cx, Localized::new("amount")).class("params-label");
+                            })
+                            .class("knob-cont");
+
+                            VStack::new(cx, |cx| {
+                                ParamKnob::new(cx, Data::params, |params| &params.spread, true)
+                                    .settings(Data::config.map(|config| config.knobs))
+                                    .default_detent(Some("detent-default"))
+                                    .class("knob")
+                                    .toggle_class(
+                                        "midi-learning",
+                                        Data::midi_learning.map(|learning| {
+                                            *learning == Some(MappableParam::Spread)
+                                        }),
+                                    )
+                                    .tooltip(|cx| {
+                                        build_tooltip(
+                                            cx,
+                                            Localized::new("tooltip-spread").arg(
+                                                "value",
+                                                param_display(|params| &params.spread),
+                                            ),
+                                        )
+                                    });
+                                Label::new(cx, Localized::new("spread")).class("params-label");
+                            })
+                            // genshin impact is the worst game in the world
+                            .class("knob-cont");
+
+                            VStack::new(cx, |cx| {
+                                ParamKnob::new(cx, Data::params, |params| &params.frequency, true)
+                                    .settings(Data::config.map(|config| config.knobs))
+                                    .default_detent(Some("detent-default"))
+                                    .class("knob")
+                                    .toggle_class(
+                                        "midi-learning",
+                                        Data::midi_learning.map(|learning| {
+                                            *learning == Some(MappableParam::Frequency)
+                                        }),
+                                    )
+                                    .tooltip(|cx| {
+                                        build_tooltip(
+                                            cx,
+                                            Localized::new("tooltip-frequency").arg(
+                                                "value",
+                                                param_display(|params| &params.frequency),
+                                            ),
+                                        )
+                                    });
+                                Label::new(cx, Localized::new("frequency")).class("params-label");
+                            })
+                            .class("knob-cont");
                         })
-                        .class("knob-cont");
+                        .padding_right(Pixels(48.0))
+                        .alignment(Alignment::Right)
+                        .gap(Pixels(24.0))
+                        .class("knob-row");
                     })
-                    .padding_right(Pixels(48.0))
-                    .alignment(Alignment::Right)
-                    .gap(Pixels(24.0));
+                    .height(Stretch(1.0));
                 })
-                .height(Stretch(1.0));
-            })
-            .class("control-panel");
-        });
+                .class("control-panel");
+            });
 
-        Binding::new(cx, Data::is_show_info_panel, |cx, show| {
-            if show.get(cx) {
-                VStack::new(cx, |cx| {
+            Binding::new(cx, Data::is_show_settings_panel, |cx, show| {
+                if show.get(cx) {
                     VStack::new(cx, |cx| {
                         VStack::new(cx, |cx| {
-                            Label::new(cx, "IM_DISPERSER").class("h1");
-                            Label::new(cx, "cuz we need a free disperser plugin").class("h2");
-                        })
-                        .alignment(Alignment::TopLeft);
-                        HStack::new(cx, |cx| {
-                            VStack::new(cx, |cx| {
-                                Button::new(cx, |cx| Label::new(cx, "i_am_dsp_repo"))
-                                    .on_press(|cx| {
-                                        cx.emit(MainViewEvent::OpenUrl(
-                                            "https://github.com/IAMMRGODIE/i_am_dsp".to_owned(),
-                                        ));
+                            Label::new(cx, Localized::new("settings")).class("h1");
+
+                            Label::new(cx, Localized::new("meters")).class("h2");
+                            HStack::new(cx, |cx| {
+                                Label::new(cx, Localized::new("peak-hold")).class("settings-label");
+                                Slider::new(
+                                    cx,
+                                    Data::params
+                                        .map(|params| params.peak_hold_ms.load(Ordering::Relaxed)),
+                                )
+                                .range(0.0..3000.0)
+                                .step(50.0)
+                                .on_change(|ex, hold_ms| {
+                                    ex.emit(MainViewEvent::SetPeakHoldMs(hold_ms))
+                                })
+                                .class("settings-slider");
+                                Label::new(
+                                    cx,
+                                    Data::params.map(|params| {
+                                        format!(
+                                            "{:.0} ms",
+                                            params.peak_hold_ms.load(Ordering::Relaxed)
+                                        )
+                                    }),
+                                )
+                                .class("settings-value");
+                            })
+                            .class("settings-row");
+
+                            HStack::new(cx, |cx| {
+                                Label::new(cx, Localized::new("peak-decay"))
+                                    .class("settings-label");
+                                Slider::new(
+                                    cx,
+                                    Data::params
+                                        .map(|params| params.peak_decay_ms.load(Ordering::Relaxed)),
+                                )
+                                .range(10.0..2000.0)
+                                .step(10.0)
+                                .on_change(|ex, decay_ms| {
+                                    ex.emit(MainViewEvent::SetPeakDecayMs(decay_ms))
+                                })
+                                .class("settings-slider");
+                                Label::new(
+                                    cx,
+                                    Data::params.map(|params| {
+                                        format!(
+                                            "{:.0} ms",
+                                            params.peak_decay_ms.load(Ordering::Relaxed)
+                                        )
+                                    }),
+                                )
+                                .class("settings-value");
+                            })
+                            .class("settings-row");
+
+                            Label::new(cx, Localized::new("midi")).class("h2");
+                            for param in MappableParam::ALL {
+                                build_midi_mapping_row(cx, param);
+                            }
+
+                            Label::new(cx, Localized::new("knobs")).class("h2");
+                            build_knob_settings(cx);
+
+                            Label::new(cx, Localized::new("appearance")).class("h2");
+                            HStack::new(cx, |cx| {
+                                Label::new(cx, Localized::new("theme")).class("settings-label");
+                                for theme in Theme::ALL {
+                                    Button::new(cx, move |cx| {
+                                        Label::new(cx, Localized::new(theme.localize_key()))
                                     })
-                                    .class("link-btn");
-                                Button::new(cx, |cx| Label::new(cx, "this_repo"))
-                                    .on_press(|cx| {
-                                        cx.emit(MainViewEvent::OpenUrl(
-                                            "https://github.com/sout233/im_disperser".to_owned(),
-                                        ));
+                                    .on_press(move |ex| {
+                                        ex.emit(MainViewEvent::SetTheme(theme));
                                     })
-                                    .class("link-btn");
-                            });
+                                    .checked(Data::theme.map(move |selected| *selected == theme))
+                                    .class("choice-btn");
+                                }
+                            })
+                            .class("settings-row");
+
+                            HStack::new(cx, |cx| {
+                                Label::new(cx, Localized::new("tooltips")).class("settings-label");
+                                Button::new(cx, |cx| {
+                                    Label::new(cx, Localized::new("tooltips-show"))
+                                })
+                                .on_press(|ex| ex.emit(MainViewEvent::ToggleTooltips))
+                                .checked(Data::config.map(|config| !config.hide_tooltips))
+                                .class("choice-btn");
+                            })
+                            .class("settings-row");
+                        })
+                        .on_press(|_| {})
+                        .class("info-panel");
+                    })
+                    .on_press(|ex| {
+                        ex.emit(MainViewEvent::ToggleSettingsPanel);
+                    })
+                    .class("info-panel-cont");
+                }
+            });
+
+            Binding::new(cx, Data::is_show_info_panel, |cx, show| {
+                if show.get(cx) {
+                    VStack::new(cx, |cx| {
+                        VStack::new(cx, |cx| {
                             VStack::new(cx, |cx| {
-                                Label::new(cx, "DSP core [i_am_dsp] by IAMMRGODIE").class("p");
-                                Label::new(cx, "VST/CLAP re-implementation & UI design by sout")
-                                    .class("p");
+                                Label::new(cx, "IM_DISPERSER").class("h1");
+                                Label::new(cx, Localized::new("tagline")).class("h2");
                             })
-                            .alignment(Alignment::BottomRight);
-                        });
+                            .alignment(Alignment::TopLeft);
+                            HStack::new(cx, |cx| {
+                                VStack::new(cx, |cx| {
+                                    Button::new(cx, |cx| Label::new(cx, "i_am_dsp_repo"))
+                                        .on_press(|cx| {
+                                            cx.emit(MainViewEvent::OpenUrl(
+                                                "https://github.com/IAMMRGODIE/i_am_dsp".to_owned(),
+                                            ));
+                                        })
+                                        .class("link-btn");
+                                    Button::new(cx, |cx| Label::new(cx, "this_repo"))
+                                        .on_press(|cx| {
+                                            cx.emit(MainViewEvent::OpenUrl(
+                                                "https://github.com/sout233/im_disperser"
+                                                    .to_owned(),
+                                            ));
+                                        })
+                                        .class("link-btn");
+                                });
+                                VStack::new(cx, |cx| {
+                                    Label::new(cx, Localized::new("credits-dsp")).class("p");
+                                    Label::new(cx, Localized::new("credits-ui")).class("p");
+                                })
+                                .alignment(Alignment::BottomRight);
+                            });
+
+                            HStack::new(cx, |cx| {
+                                Label::new(cx, Localized::new("ui-scale")).class("settings-label");
+                                for (scale, label) in UI_SCALES {
+                                    Button::new(cx, move |cx| Label::new(cx, label))
+                                        .on_press(move |ex| {
+                                            ex.emit(MainViewEvent::SetUiScale(scale));
+                                        })
+                                        .checked(Data::params.map(move |params| {
+                                            (params.editor_state.user_scale_factor() - scale).abs()
+                                                < 1e-3
+                                        }))
+                                        .class("choice-btn");
+                                }
+                            })
+                            .class("settings-row");
+
+                            HStack::new(cx, |cx| {
+                                Label::new(cx, Localized::new("language")).class("settings-label");
+                                Button::new(cx, |cx| {
+                                    Label::new(cx, Localized::new("language-auto"))
+                                })
+                                .on_press(|ex| {
+                                    ex.emit(MainViewEvent::SetLanguage(None));
+                                })
+                                .checked(Data::language.map(|selected| selected.is_none()))
+                                .class("choice-btn");
+                                for language in Language::ALL {
+                                    Button::new(cx, move |cx| Label::new(cx, language.label()))
+                                        .on_press(move |ex| {
+                                            ex.emit(MainViewEvent::SetLanguage(Some(language)));
+                                        })
+                                        .checked(
+                                            Data::language
+                                                .map(move |selected| *selected == Some(language)),
+                                        )
+                                        .class("choice-btn");
+                                }
+                            })
+                            .class("settings-row");
+                        })
+                        .on_press(|_| {})
+                        .class("info-panel");
                     })
-                    .class("info-panel");
-                })
-                .on_press(|ex| {
-                    ex.emit(MainViewEvent::ToggleInfoPanel);
-                })
-                .class("info-panel-cont");
+                    .on_press(|ex| {
+                        ex.emit(MainViewEvent::ToggleInfoPanel);
+                    })
+                    .class("info-panel-cont");
+                }
+            });
+
+            Binding::new(cx, Data::is_show_preset_list, |cx, show| {
+                if show.get(cx) {
+                    VStack::new(cx, build_preset_list)
+                        .on_press(|ex| {
+                            ex.emit(MainViewEvent::TogglePresetList);
+                        })
+                        .class("preset-list-cont");
+                }
+            });
+
+            // It stays on top of the overlays
+            ResizeCorner::new(cx, |ex, (width, height)| {
+                ex.emit(MainViewEvent::ResizeWindow(width, height));
+            });
+        })
+        .class("theme-root");
+        for theme in Theme::ALL {
+            if let Some(class_name) = theme.class_name() {
+                root = root.toggle_class(class_name, Data::theme.map(move |t| *t == theme));
             }
-        });
+        }
+        root.toggle_class(
+            "hide-tooltips",
+            Data::config.map(|config| config.hide_tooltips),
+        );
 
         // VStack::new(cx, |cx| {
         //     Label::new(cx, "IM_DISPERSER")
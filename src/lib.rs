@@ -1,6 +1,7 @@
 use atomic_float::AtomicF32;
 use nih_plug::prelude::*;
-use std::sync::Arc;
+use std::sync::atomic::AtomicU8;
+use std::sync::{Arc, RwLock};
 use vizia_plug::ViziaState;
 
 use i_am_dsp::{
@@ -8,10 +9,29 @@ use i_am_dsp::{
     real_time_demo::SimpleContext,
 };
 
+mod ab;
+mod analysis;
+mod config;
 mod editor;
+mod localize;
+mod midi;
+mod preset;
+mod theme;
+mod undo;
 mod widgets;
 
-const PEAK_METER_DECAY_MS: f64 = 150.0;
+use ab::AbSlots;
+use analysis::loudness::{LoudnessMeter, LoudnessReadings};
+use analysis::peak::{DEFAULT_PEAK_DECAY_MS, DEFAULT_PEAK_HOLD_MS, PeakMeterState, decay_weight};
+use analysis::taps::SignalTaps;
+use midi::{MappableParam, MidiMappings};
+use preset::PresetId;
+
+/// How long frequency and spread take to glide to a new value, this keeps preset and A/B
+/// switches free of clicks.
+const PARAMETER_SMOOTHING_MS: f32 = 40.0;
+/// The number of samples between filter updates while frequency or spread are smoothing.
+const FILTER_UPDATE_INTERVAL: usize = 32;
 
 pub struct DisperserPlugin {
     params: Arc<DisperserParams>,
@@ -20,14 +40,53 @@ pub struct DisperserPlugin {
     sample_rate: f32,
 
     peak_meter_decay_weight: f32,
+    /// The decay time `peak_meter_decay_weight` was computed for.
+    peak_meter_decay_ms: f32,
     pre_signal: Arc<AtomicF32>,
     post_signal: Arc<AtomicF32>,
+    taps: Arc<SignalTaps>,
+
+    input_loudness: LoudnessMeter,
+    output_loudness: LoudnessMeter,
+    loudness: Arc<LoudnessReadings>,
+    output_peaks: Arc<PeakMeterState>,
+
+    /// Normalized values set by mapped CCs, these take precedence over the host's values until
+    /// the host changes the parameter. Frequency and spread glide to them through their
+    /// smoothers.
+    midi_overrides: [Option<f32>; MappableParam::ALL.len()],
+    /// The host's normalized values when the overrides were last checked.
+    host_values: [f32; MappableParam::ALL.len()],
+    /// The generation of the last CC value sent by the editor whose echo has been seen, see
+    /// [`midi::PendingCc`].
+    acked_midi_sends: [u32; MappableParam::ALL.len()],
 }
 
 #[derive(Params)]
 struct DisperserParams {
     #[persist = "editor-state"]
     editor_state: Arc<ViziaState>,
+    /// The editor window's size in logical pixels, before UI scaling. `editor_state` reads it
+    /// whenever the window is opened or resized.
+    #[persist = "editor-size"]
+    editor_size: Arc<RwLock<(u32, u32)>>,
+    /// The index of the view selected in the editor's spectrum panel.
+    #[persist = "visualizer-tab"]
+    visualizer_tab: AtomicU8,
+    #[persist = "midi-mappings"]
+    midi_mappings: MidiMappings,
+    /// The preset last loaded or saved in the editor.
+    #[persist = "preset"]
+    preset: RwLock<Option<PresetId>>,
+    #[persist = "ab-slots"]
+    ab_slots: RwLock<AbSlots>,
+    /// How long the output peak meter holds its peak marker, in milliseconds. Zero disables the
+    /// hold.
+    #[persist = "peak-hold-ms"]
+    peak_hold_ms: AtomicF32,
+    /// The time it takes for the output peak meter to decay by 12 dB, in milliseconds.
+    #[persist = "peak-decay-ms"]
+    peak_decay_ms: AtomicF32,
 
     #[id = "frequency"]
     pub frequency: FloatParam,
@@ -47,16 +106,35 @@ impl Default for DisperserPlugin {
             sample_rate: 44100.0,
 
             peak_meter_decay_weight: 1.0,
+            peak_meter_decay_ms: 0.0,
             pre_signal: Arc::new(AtomicF32::new(util::MINUS_INFINITY_DB)),
             post_signal: Arc::new(AtomicF32::new(util::MINUS_INFINITY_DB)),
+            taps: Arc::new(SignalTaps::default()),
+
+            input_loudness: LoudnessMeter::new(44100.0),
+            output_loudness: LoudnessMeter::new(44100.0),
+            loudness: Arc::new(LoudnessReadings::default()),
+            output_peaks: Arc::new(PeakMeterState::default()),
+
+            midi_overrides: [None; MappableParam::ALL.len()],
+            host_values: [f32::NAN; MappableParam::ALL.len()],
+            acked_midi_sends: [0; MappableParam::ALL.len()],
         }
     }
 }
 
 impl Default for DisperserParams {
     fn default() -> Self {
+        let editor_size = Arc::new(RwLock::new(editor::DEFAULT_SIZE));
         Self {
-            editor_state: editor::default_state(),
+            editor_state: editor::default_state(editor_size.clone()),
+            editor_size,
+            visualizer_tab: AtomicU8::new(0),
+            midi_mappings: MidiMappings::default(),
+            preset: RwLock::new(None),
+            ab_slots: RwLock::new(AbSlots::default()),
+            peak_hold_ms: AtomicF32::new(DEFAULT_PEAK_HOLD_MS),
+            peak_decay_ms: AtomicF32::new(DEFAULT_PEAK_DECAY_MS),
 
             frequency: FloatParam::new(
                 "Frequency",
@@ -67,6 +145,7 @@ impl Default for DisperserParams {
                     factor: FloatRange::skew_factor(-2.0),
                 },
             )
+            .with_smoother(SmoothingStyle::Logarithmic(PARAMETER_SMOOTHING_MS))
             .with_unit(" Hz"),
 
             spread: FloatParam::new(
@@ -78,6 +157,7 @@ impl Default for DisperserParams {
                     factor: FloatRange::skew_factor(-2.0),
                 },
             )
+            .with_smoother(SmoothingStyle::Logarithmic(PARAMETER_SMOOTHING_MS))
             .with_unit(" Hz"),
 
             amount: IntParam::new("Amount", 80, IntRange::Linear { min: 0, max: 100 }),
@@ -99,6 +179,7 @@ impl Plugin for DisperserPlugin {
         ..AudioIOLayout::const_default()
     }];
 
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
     const SAMPLE_ACCURATE_AUTOMATION: bool = true;
 
     type SysExMessage = ();
@@ -113,6 +194,9 @@ impl Plugin for DisperserPlugin {
             self.params.clone(),
             self.pre_signal.clone(),
             self.post_signal.clone(),
+            self.taps.clone(),
+            self.loudness.clone(),
+            self.output_peaks.clone(),
             self.params.editor_state.clone(),
         )
     }
@@ -125,10 +209,13 @@ impl Plugin for DisperserPlugin {
     ) -> bool {
         self.sample_rate = buffer_config.sample_rate;
         self.disperser = Disperser::<2>::new(self.sample_rate as usize);
+        self.taps
+            .sample_rate
+            .store(self.sample_rate, std::sync::atomic::Ordering::Relaxed);
+        self.input_loudness = LoudnessMeter::new(self.sample_rate);
+        self.output_loudness = LoudnessMeter::new(self.sample_rate);
 
-        self.peak_meter_decay_weight = 0.25f64
-            .powf((buffer_config.sample_rate as f64 * PEAK_METER_DECAY_MS / 1000.0).recip())
-            as f32;
+        self.update_peak_meter_decay(true);
 
         true
     }
@@ -137,14 +224,10 @@ impl Plugin for DisperserPlugin {
         &mut self,
         buffer: &mut Buffer,
         _aux: &mut AuxiliaryBuffers,
-        _context: &mut impl ProcessContext<Self>,
+        context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
-        let freq = self.params.frequency.value();
-        let spread = self.params.spread.value();
-        let amount = self.params.amount.value();
-
-        self.disperser.set_filter_parameters(freq, spread);
-        self.disperser.set_biquad_count(amount as usize);
+        self.clear_stale_midi_overrides();
+        self.update_filter_parameters();
 
         let mut info = ProcessInfos::new();
         info.sample_rate = self.sample_rate as usize;
@@ -156,9 +239,25 @@ impl Plugin for DisperserPlugin {
 
         let mut dsp_ctx: Box<dyn DspContext> = Box::new(simple_ctx);
 
+        self.update_peak_meter_decay(false);
+        if self.loudness.take_reset_request() {
+            self.input_loudness.reset();
+            self.output_loudness.reset();
+        }
+
         let mut amplitude = 0.0;
         let mut original_amplitude = 0.0;
+        let mut channel_peaks = [0.0f32; 2];
         let channels = buffer.channels();
+        let editor_open = self.params.editor_state.is_open();
+        // Only do the work for the view that is actually shown
+        let visualizer_tab = editor::VisualizerTab::from_index(
+            self.params
+                .visualizer_tab
+                .load(std::sync::atomic::Ordering::Relaxed),
+        );
+        let fill_pre_tap = editor_open && visualizer_tab.uses_pre_tap();
+        let track_signal_levels = editor_open && visualizer_tab == editor::VisualizerTab::Waveform;
 
         // TODO: impl for other channels
         if channels == 2 {
@@ -167,7 +266,37 @@ impl Plugin for DisperserPlugin {
             let left_samples = &mut left_chan[0];
             let right_samples = &mut right_chan[0];
 
-            for (l, r) in left_samples.iter_mut().zip(right_samples.iter_mut()) {
+            let num_samples = left_samples.len();
+            let mut next_event = context.next_event();
+            let mut next_filter_update = 0;
+            for (sample_idx, (l, r)) in left_samples
+                .iter_mut()
+                .zip(right_samples.iter_mut())
+                .enumerate()
+            {
+                // Mapped CCs are picked up on the sample they arrive at, and the glide to them
+                // starts right there instead of at the next regular filter update
+                while let Some(event) = next_event {
+                    if event.timing() > sample_idx as u32 {
+                        break;
+                    }
+                    if let NoteEvent::MidiCC { cc, value, .. } = event {
+                        if self.handle_midi_cc(cc, value) {
+                            next_filter_update = sample_idx;
+                        }
+                    }
+                    next_event = context.next_event();
+                }
+
+                // Recomputing the filters every sample would be too expensive, so the smoothed
+                // values are applied in small steps. The last step of a block only covers the
+                // samples left in it, or short blocks would finish the glide early.
+                if sample_idx >= next_filter_update && self.is_smoothing() {
+                    let step = FILTER_UPDATE_INTERVAL.min(num_samples - sample_idx);
+                    self.step_filter_parameters(step as u32);
+                    next_filter_update = sample_idx + step;
+                }
+
                 let mut frame = [*l, *r];
                 let other_inputs: &[&[f32; 2]] = &[];
 
@@ -176,6 +305,11 @@ impl Plugin for DisperserPlugin {
                     original_amplitude = current_amp;
                 }
 
+                if fill_pre_tap {
+                    self.taps.pre.push((*l + *r) * 0.5);
+                }
+                self.input_loudness.process(*l, *r);
+
                 self.disperser
                     .process(&mut frame, other_inputs, &mut dsp_ctx);
 
@@ -186,11 +320,32 @@ impl Plugin for DisperserPlugin {
                 if current_amp > amplitude {
                     amplitude = current_amp;
                 }
+                channel_peaks[0] = channel_peaks[0].max(l.abs());
+                channel_peaks[1] = channel_peaks[1].max(r.abs());
+
+                if editor_open {
+                    self.taps.post.push_frame([*l, *r]);
+                }
+                self.output_loudness.process(*l, *r);
+            }
+
+            // The post tap still moved on, the pre tap has to stay at the same position
+            if editor_open && !fill_pre_tap {
+                self.taps.pre.skip(num_samples);
             }
         }
 
+        self.input_loudness.publish(&self.loudness.input);
+        self.output_loudness.publish(&self.loudness.output);
+        // Kept up to date while the editor is closed, so it still shows the clips it missed
+        self.output_peaks.update(
+            channel_peaks,
+            buffer.samples(),
+            self.peak_meter_decay_weight,
+        );
+
         for channel_samples in buffer.iter_samples() {
-            if self.params.editor_state.is_open() {
+            if track_signal_levels {
                 let num_samples = channel_samples.len();
                 original_amplitude = (original_amplitude / num_samples as f32).abs();
                 let current_peak_meter = self.pre_signal.load(std::sync::atomic::Ordering::Relaxed);
@@ -224,6 +379,162 @@ impl Plugin for DisperserPlugin {
     }
 }
 
+impl DisperserPlugin {
+    fn is_smoothing(&self) -> bool {
+        self.params.frequency.smoothed.is_smoothing() || self.params.spread.smoothed.is_smoothing()
+    }
+
+    /// Moves the smoothed parameters on by `samples` and updates the filters.
+    fn step_filter_parameters(&mut self, samples: u32) {
+        self.params.frequency.smoothed.next_step(samples);
+        self.params.spread.smoothed.next_step(samples);
+        self.update_filter_parameters();
+    }
+
+    fn update_filter_parameters(&mut self) {
+        let params = &self.params;
+        let freq = params.frequency.smoothed.previous_value();
+        let spread = params.spread.smoothed.previous_value();
+        let amount = midi_or_host_value(
+            &params.amount,
+            self.midi_overrides[MappableParam::Amount as usize],
+            params.amount.modulated_plain_value(),
+        );
+
+        self.disperser.set_filter_parameters(freq, spread);
+        self.disperser.set_biquad_count(amount.max(0) as usize);
+    }
+
+    /// Returns whether the CC was mapped to a parameter.
+    fn handle_midi_cc(&mut self, cc: u8, value: f32) -> bool {
+        let Some((param, normalized_value)) = self.params.midi_mappings.handle_cc(cc, value) else {
+            return false;
+        };
+
+        self.midi_overrides[param as usize] = Some(normalized_value);
+        self.apply_midi_override(param, normalized_value);
+        true
+    }
+
+    /// Glides frequency and spread to a CC-driven value, the amount is stepped anyway and
+    /// switches right away.
+    fn apply_midi_override(&mut self, param: MappableParam, normalized_value: f32) {
+        let (params, sample_rate) = (&self.params, self.sample_rate);
+        match param {
+            MappableParam::Frequency => params.frequency.smoothed.set_target(
+                sample_rate,
+                params.frequency.preview_plain(normalized_value),
+            ),
+            MappableParam::Spread => params
+                .spread
+                .smoothed
+                .set_target(sample_rate, params.spread.preview_plain(normalized_value)),
+            MappableParam::Amount => self.update_filter_parameters(),
+        }
+    }
+
+    /// Drops the CC overrides of parameters the host has changed since the last block. The
+    /// editor sends CC values on to the host a little later, the first change after a send is
+    /// taken as its echo.
+    fn clear_stale_midi_overrides(&mut self) {
+        for param in MappableParam::ALL {
+            let host_value = self.params.normalized_value(param);
+            if host_value == self.host_values[param as usize] {
+                continue;
+            }
+            self.host_values[param as usize] = host_value;
+
+            let mappings = &self.params.midi_mappings;
+            let sent = mappings.last_sent(param);
+            let is_echo = sent != self.acked_midi_sends[param as usize];
+            self.acked_midi_sends[param as usize] = sent;
+            if is_echo {
+                if sent == mappings.generation(param) {
+                    // The host has caught up with the latest CC and can take over again
+                    self.midi_overrides[param as usize] = None;
+                } else if let Some(normalized_value) = self.midi_overrides[param as usize] {
+                    // The host's change also retargeted the smoother, back to an older CC value
+                    self.apply_midi_override(param, normalized_value);
+                }
+            } else {
+                self.midi_overrides[param as usize] = None;
+                self.params.midi_mappings.drop_pending(param);
+            }
+        }
+    }
+
+    /// Recomputes the peak meter decay coefficient if the decay time was changed in the editor.
+    fn update_peak_meter_decay(&mut self, force: bool) {
+        let decay_ms = self
+            .params
+            .peak_decay_ms
+            .load(std::sync::atomic::Ordering::Relaxed);
+        if force || decay_ms != self.peak_meter_decay_ms {
+            self.peak_meter_decay_ms = decay_ms;
+            self.peak_meter_decay_weight = decay_weight(self.sample_rate, decay_ms);
+        }
+    }
+}
+
+impl DisperserParams {
+    fn normalized_value(&self, param: MappableParam) -> f32 {
+        match param {
+            MappableParam::Frequency => self.frequency.modulated_normalized_value(),
+            MappableParam::Spread => self.spread.modulated_normalized_value(),
+            MappableParam::Amount => self.amount.modulated_normalized_value(),
+        }
+    }
+
+    fn unmodulated_normalized_value(&self, param: MappableParam) -> f32 {
+        match param {
+            MappableParam::Frequency => self.frequency.unmodulated_normalized_value(),
+            MappableParam::Spread => self.spread.unmodulated_normalized_value(),
+            MappableParam::Amount => self.amount.unmodulated_normalized_value(),
+        }
+    }
+
+    /// `value` as the parameter stores it, the amount rounds it to a whole step.
+    fn round_normalized(&self, param: MappableParam, value: f32) -> f32 {
+        match param {
+            MappableParam::Frequency => self
+                .frequency
+                .preview_normalized(self.frequency.preview_plain(value)),
+            MappableParam::Spread => self
+                .spread
+                .preview_normalized(self.spread.preview_plain(value)),
+            MappableParam::Amount => self
+                .amount
+                .preview_normalized(self.amount.preview_plain(value)),
+        }
+    }
+
+    /// The parameter behind a pointer from a parameter event.
+    fn param_for_ptr(&self, param_ptr: ParamPtr) -> Option<MappableParam> {
+        MappableParam::ALL
+            .into_iter()
+            .find(|param| self.param_ptr(*param) == param_ptr)
+    }
+
+    fn param_ptr(&self, param: MappableParam) -> ParamPtr {
+        match param {
+            MappableParam::Frequency => self.frequency.as_ptr(),
+            MappableParam::Spread => self.spread.as_ptr(),
+            MappableParam::Amount => self.amount.as_ptr(),
+        }
+    }
+}
+
+/// The value the DSP should use for a parameter that can be overridden by a CC.
+fn midi_or_host_value<P: Param>(
+    param: &P,
+    midi_override: Option<f32>,
+    host_value: P::Plain,
+) -> P::Plain {
+    midi_override.map_or(host_value, |normalized_value| {
+        param.preview_plain(normalized_value)
+    })
+}
+
 impl ClapPlugin for DisperserPlugin {
     const CLAP_ID: &'static str = "top.soout.godiedsp.disperser";
     const CLAP_DESCRIPTION: Option<&'static str> = Some("Phase Disperser Effect");
@@ -0,0 +1,58 @@
+//! The languages the editor is translated to. The Fluent bundles live in `resources/<locale>/`,
+//! the same layout the installer uses.
+
+use serde::{Deserialize, Serialize};
+use vizia_plug::vizia::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Data)]
+pub enum Language {
+    #[serde(rename = "en-US")]
+    English,
+    #[serde(rename = "zh-CN")]
+    SimplifiedChinese,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::SimplifiedChinese];
+
+    /// The name of the language in that language, so it can be found regardless of the current one.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::SimplifiedChinese => "简体中文",
+        }
+    }
+
+    pub fn langid(&self) -> LanguageIdentifier {
+        match self {
+            Language::English => langid!("en-US"),
+            Language::SimplifiedChinese => langid!("zh-CN"),
+        }
+    }
+
+    /// Picks a language from the system locale, falling back to English for anything we don't
+    /// have a translation for.
+    pub fn detect() -> Self {
+        match sys_locale::get_locale() {
+            Some(locale) if locale.to_lowercase().starts_with("zh") => Language::SimplifiedChinese,
+            _ => Language::English,
+        }
+    }
+
+    /// Resolves the language setting, where `None` means following the system locale.
+    pub fn resolve(choice: Option<Language>) -> Self {
+        choice.unwrap_or_else(Language::detect)
+    }
+}
+
+/// Registers the translations of every [`Language`].
+pub fn add_translations(cx: &mut Context) {
+    cx.add_translation(
+        langid!("en-US"),
+        include_str!("resources/en-US/main.flt").to_owned(),
+    );
+    cx.add_translation(
+        langid!("zh-CN"),
+        include_str!("resources/zh-CN/main.flt").to_owned(),
+    );
+}
@@ -0,0 +1,318 @@
+//! MIDI learn and the CC to parameter mappings. Every CC number has its own slot of atomics, so
+//! the audio thread can read and learn mappings without locking or allocating.
+
+use atomic_float::AtomicF32;
+use nih_plug::params::persist::PersistentField;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU8, AtomicU32, AtomicU64, Ordering};
+use vizia_plug::vizia::prelude::Data;
+
+const NUM_CCS: usize = 128;
+/// Stored in place of a parameter index when a slot is unmapped or nothing is being learned.
+const NO_PARAM: u8 = u8::MAX;
+/// Stored in place of a [`PendingCc`] when there is none.
+const NO_PENDING: u64 = u64::MAX;
+
+/// The parameters that can be mapped to a CC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Data)]
+#[serde(rename_all = "kebab-case")]
+pub enum MappableParam {
+    Frequency,
+    Spread,
+    Amount,
+}
+
+impl MappableParam {
+    pub const ALL: [MappableParam; 3] = [
+        MappableParam::Frequency,
+        MappableParam::Spread,
+        MappableParam::Amount,
+    ];
+
+    /// The Fluent message id of the parameter's name.
+    pub fn localize_key(&self) -> &'static str {
+        match self {
+            MappableParam::Frequency => "frequency",
+            MappableParam::Spread => "spread",
+            MappableParam::Amount => "amount",
+        }
+    }
+
+    fn from_index(index: u8) -> Option<Self> {
+        Self::ALL.get(index as usize).copied()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Data)]
+#[serde(rename_all = "kebab-case")]
+pub enum MappingCurve {
+    #[default]
+    Linear,
+    /// Finer control at the low end of the range.
+    Exponential,
+    /// Finer control at the high end of the range.
+    Logarithmic,
+}
+
+impl MappingCurve {
+    pub const ALL: [MappingCurve; 3] = [
+        MappingCurve::Linear,
+        MappingCurve::Exponential,
+        MappingCurve::Logarithmic,
+    ];
+
+    /// The Fluent message id of the curve's name.
+    pub fn localize_key(&self) -> &'static str {
+        match self {
+            MappingCurve::Linear => "curve-linear",
+            MappingCurve::Exponential => "curve-exponential",
+            MappingCurve::Logarithmic => "curve-logarithmic",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        Self::ALL[(*self as usize + 1) % Self::ALL.len()]
+    }
+
+    fn from_index(index: u8) -> Self {
+        Self::ALL.get(index as usize).copied().unwrap_or_default()
+    }
+
+    fn apply(&self, x: f32) -> f32 {
+        match self {
+            MappingCurve::Linear => x,
+            MappingCurve::Exponential => x * x,
+            MappingCurve::Logarithmic => 1.0 - (1.0 - x) * (1.0 - x),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Data)]
+pub struct MidiMapping {
+    pub cc: u8,
+    pub param: MappableParam,
+    /// The normalized parameter value at CC value 0.
+    pub min: f32,
+    /// The normalized parameter value at CC value 127. This may be below `min` to invert the CC.
+    pub max: f32,
+    pub curve: MappingCurve,
+}
+
+impl MidiMapping {
+    fn new(cc: u8, param: MappableParam) -> Self {
+        Self {
+            cc,
+            param,
+            min: 0.0,
+            max: 1.0,
+            curve: MappingCurve::Linear,
+        }
+    }
+
+    /// Maps a CC value in `[0, 1]` to the parameter's normalized value.
+    pub fn normalized_value(&self, cc_value: f32) -> f32 {
+        self.min + (self.max - self.min) * self.curve.apply(cc_value.clamp(0.0, 1.0))
+    }
+}
+
+/// A CC-driven value for the editor to send to the host. Every CC a parameter receives gets the
+/// next generation, so the audio thread can tell the host's echo of this value apart from other
+/// changes without comparing values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PendingCc {
+    pub normalized_value: f32,
+    pub generation: u32,
+}
+
+impl PendingCc {
+    fn pack(&self) -> u64 {
+        ((self.generation as u64) << 32) | self.normalized_value.to_bits() as u64
+    }
+
+    fn unpack(packed: u64) -> Option<Self> {
+        (packed != NO_PENDING).then(|| Self {
+            normalized_value: f32::from_bits(packed as u32),
+            generation: (packed >> 32) as u32,
+        })
+    }
+}
+
+struct CcSlot {
+    param: AtomicU8,
+    min: AtomicF32,
+    max: AtomicF32,
+    curve: AtomicU8,
+}
+
+impl Default for CcSlot {
+    fn default() -> Self {
+        Self {
+            param: AtomicU8::new(NO_PARAM),
+            min: AtomicF32::new(0.0),
+            max: AtomicF32::new(1.0),
+            curve: AtomicU8::new(0),
+        }
+    }
+}
+
+impl CcSlot {
+    fn load(&self, cc: u8) -> Option<MidiMapping> {
+        let param = MappableParam::from_index(self.param.load(Ordering::Relaxed))?;
+        Some(MidiMapping {
+            cc,
+            param,
+            min: self.min.load(Ordering::Relaxed),
+            max: self.max.load(Ordering::Relaxed),
+            curve: MappingCurve::from_index(self.curve.load(Ordering::Relaxed)),
+        })
+    }
+
+    fn store(&self, mapping: &MidiMapping) {
+        self.min.store(mapping.min, Ordering::Relaxed);
+        self.max.store(mapping.max, Ordering::Relaxed);
+        self.curve.store(mapping.curve as u8, Ordering::Relaxed);
+        self.param.store(mapping.param as u8, Ordering::Relaxed);
+    }
+
+    fn clear(&self) {
+        self.param.store(NO_PARAM, Ordering::Relaxed);
+    }
+}
+
+/// The CC mappings, persisted in the plugin state as a list of [`MidiMapping`]s. Every parameter
+/// is mapped to at most one CC.
+pub struct MidiMappings {
+    slots: [CcSlot; NUM_CCS],
+    /// The parameter the next CC gets mapped to.
+    learn_target: AtomicU8,
+    /// The latest [`PendingCc`] of every parameter that the editor has yet to send to the host,
+    /// packed into a single atomic.
+    pending: [AtomicU64; MappableParam::ALL.len()],
+    /// The generation of the latest CC of every parameter.
+    generations: [AtomicU32; MappableParam::ALL.len()],
+    /// The generation of the CC value the editor last sent to the host for every parameter. The
+    /// host echoes these back, which mustn't be mistaken for the user changing the parameter.
+    sent: [AtomicU32; MappableParam::ALL.len()],
+}
+
+impl Default for MidiMappings {
+    fn default() -> Self {
+        Self {
+            slots: std::array::from_fn(|_| CcSlot::default()),
+            learn_target: AtomicU8::new(NO_PARAM),
+            pending: std::array::from_fn(|_| AtomicU64::new(NO_PENDING)),
+            generations: std::array::from_fn(|_| AtomicU32::new(0)),
+            sent: std::array::from_fn(|_| AtomicU32::new(0)),
+        }
+    }
+}
+
+impl MidiMappings {
+    pub fn mapping(&self, param: MappableParam) -> Option<MidiMapping> {
+        self.mappings()
+            .into_iter()
+            .find(|mapping| mapping.param == param)
+    }
+
+    pub fn mappings(&self) -> Vec<MidiMapping> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(cc, slot)| slot.load(cc as u8))
+            .collect()
+    }
+
+    /// Changes the range or curve of an existing mapping.
+    pub fn update(&self, mapping: MidiMapping) {
+        if let Some(slot) = self.slots.get(mapping.cc as usize) {
+            slot.store(&mapping);
+        }
+    }
+
+    pub fn unmap(&self, param: MappableParam) {
+        for slot in &self.slots {
+            if slot.param.load(Ordering::Relaxed) == param as u8 {
+                slot.clear();
+            }
+        }
+    }
+
+    /// Maps the next incoming CC to `param`, or cancels learning if it's `None`.
+    pub fn learn(&self, param: Option<MappableParam>) {
+        self.learn_target.store(
+            param.map_or(NO_PARAM, |param| param as u8),
+            Ordering::Relaxed,
+        );
+    }
+
+    pub fn learning(&self) -> Option<MappableParam> {
+        MappableParam::from_index(self.learn_target.load(Ordering::Relaxed))
+    }
+
+    /// Handles a CC on the audio thread, learning it first if a parameter is waiting for one.
+    /// Returns the mapped parameter and its new normalized value.
+    pub fn handle_cc(&self, cc: u8, value: f32) -> Option<(MappableParam, f32)> {
+        let slot = self.slots.get(cc as usize)?;
+        if let Some(param) =
+            MappableParam::from_index(self.learn_target.swap(NO_PARAM, Ordering::Relaxed))
+        {
+            self.unmap(param);
+            slot.store(&MidiMapping::new(cc, param));
+        }
+
+        let mapping = slot.load(cc)?;
+        let pending = PendingCc {
+            normalized_value: mapping.normalized_value(value),
+            generation: self.generations[mapping.param as usize]
+                .fetch_add(1, Ordering::Relaxed)
+                .wrapping_add(1),
+        };
+        self.pending[mapping.param as usize].store(pending.pack(), Ordering::Relaxed);
+
+        Some((mapping.param, pending.normalized_value))
+    }
+
+    /// The generation of the latest CC `param` received.
+    pub fn generation(&self, param: MappableParam) -> u32 {
+        self.generations[param as usize].load(Ordering::Relaxed)
+    }
+
+    /// Takes the latest CC-driven value of `param` that hasn't been sent to the host yet.
+    pub fn take_pending(&self, param: MappableParam) -> Option<PendingCc> {
+        PendingCc::unpack(self.pending[param as usize].swap(NO_PENDING, Ordering::Relaxed))
+    }
+
+    /// Records that the editor is about to send `pending` to the host.
+    pub fn mark_sent(&self, param: MappableParam, pending: PendingCc) {
+        self.sent[param as usize].store(pending.generation, Ordering::Relaxed);
+    }
+
+    /// Forgets the CC-driven value of `param` that hasn't been sent yet, once the host has
+    /// changed the parameter since. Sending it later would undo the host's change.
+    pub fn drop_pending(&self, param: MappableParam) {
+        self.pending[param as usize].store(NO_PENDING, Ordering::Relaxed);
+    }
+
+    /// The generation of the CC value the editor last sent to the host for `param`.
+    pub fn last_sent(&self, param: MappableParam) -> u32 {
+        self.sent[param as usize].load(Ordering::Relaxed)
+    }
+}
+
+impl<'a> PersistentField<'a, Vec<MidiMapping>> for MidiMappings {
+    fn set(&self, new_value: Vec<MidiMapping>) {
+        for slot in &self.slots {
+            slot.clear();
+        }
+        for mapping in &new_value {
+            self.update(*mapping);
+        }
+    }
+
+    fn map<F, R>(&self, f: F) -> R
+    where
+        F: Fn(&Vec<MidiMapping>) -> R,
+    {
+        f(&self.mappings())
+    }
+}
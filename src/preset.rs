@@ -0,0 +1,340 @@
+//! Presets, stored as versioned JSON. The factory bank is compiled into the plugin and the user's
+//! presets are files in the platform's data directory, one per preset.
+
+use nih_plug::nih_log;
+use nih_plug::prelude::{Param, ParamPtr};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use vizia_plug::vizia::prelude::Data;
+
+use crate::DisperserParams;
+
+/// The current version of the preset format. Presets from newer versions are skipped.
+pub const PRESET_VERSION: u32 = 1;
+
+const PRESET_DIR_NAME: &str = "im_disperser";
+const PRESET_SUBDIR_NAME: &str = "presets";
+const PRESET_EXTENSION: &str = "json";
+/// How far apart two normalized values can be and still count as the same. Values that went
+/// through the host or a preset file don't always come back bit for bit.
+const MATCH_TOLERANCE: f32 = 1e-4;
+
+const FACTORY_PRESETS: [&str; 7] = [
+    include_str!("resources/presets/init.json"),
+    include_str!("resources/presets/subtle-smear.json"),
+    include_str!("resources/presets/wide-sweep.json"),
+    include_str!("resources/presets/deep-chirp.json"),
+    include_str!("resources/presets/kick-punch.json"),
+    include_str!("resources/presets/hat-sizzle.json"),
+    include_str!("resources/presets/laser-zap.json"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Data)]
+#[serde(rename_all = "kebab-case")]
+pub enum PresetSource {
+    Factory,
+    User,
+}
+
+/// Identifies a preset. The plugin state remembers this rather than an index, since the user's
+/// presets can change between sessions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Data)]
+pub struct PresetId {
+    pub name: String,
+    pub source: PresetSource,
+}
+
+/// The plain value of every parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Data)]
+pub struct PresetValues {
+    pub frequency: f32,
+    pub spread: f32,
+    pub amount: i32,
+}
+
+impl PresetValues {
+    pub fn from_params(params: &DisperserParams) -> Self {
+        Self {
+            frequency: params.frequency.unmodulated_plain_value(),
+            spread: params.spread.unmodulated_plain_value(),
+            amount: params.amount.unmodulated_plain_value(),
+        }
+    }
+
+    /// The normalized value of every parameter, for the editor to send to the host.
+    pub fn normalized_values(&self, params: &DisperserParams) -> [(ParamPtr, f32); 3] {
+        [
+            (
+                params.frequency.as_ptr(),
+                params.frequency.preview_normalized(self.frequency),
+            ),
+            (
+                params.spread.as_ptr(),
+                params.spread.preview_normalized(self.spread),
+            ),
+            (
+                params.amount.as_ptr(),
+                params.amount.preview_normalized(self.amount),
+            ),
+        ]
+    }
+
+    /// Whether both set the parameters to the same values, give or take rounding.
+    pub fn matches(&self, other: &Self, params: &DisperserParams) -> bool {
+        self.normalized_values(params)
+            .iter()
+            .zip(other.normalized_values(params))
+            .all(|((_, a), (_, b))| (a - b).abs() < MATCH_TOLERANCE)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Data)]
+pub struct Preset {
+    pub name: String,
+    pub source: PresetSource,
+    pub values: PresetValues,
+    /// The file a user preset was loaded from, `None` for the factory presets. The file name is
+    /// derived from the name when the preset is created, but it's only ever read back from here.
+    pub path: Option<PathBuf>,
+}
+
+/// The JSON layout of a preset.
+#[derive(Serialize, Deserialize)]
+struct PresetFile {
+    version: u32,
+    name: String,
+    values: PresetValues,
+}
+
+impl Preset {
+    pub fn id(&self) -> PresetId {
+        PresetId {
+            name: self.name.clone(),
+            source: self.source,
+        }
+    }
+
+    fn parse(json: &str, source: PresetSource) -> Result<Self, String> {
+        let file: PresetFile = serde_json::from_str(json).map_err(|err| err.to_string())?;
+        if file.version > PRESET_VERSION {
+            return Err(format!(
+                "preset version {} is newer than {PRESET_VERSION}",
+                file.version
+            ));
+        }
+
+        Ok(Self {
+            name: file.name,
+            source,
+            values: file.values,
+            path: None,
+        })
+    }
+
+    fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(&PresetFile {
+            version: PRESET_VERSION,
+            name: self.name.clone(),
+            values: self.values,
+        })
+    }
+}
+
+/// The factory bank followed by the user's presets sorted by name.
+pub fn load_all() -> Vec<Preset> {
+    let mut presets: Vec<Preset> = FACTORY_PRESETS
+        .iter()
+        .filter_map(|json| match Preset::parse(json, PresetSource::Factory) {
+            Ok(preset) => Some(preset),
+            Err(err) => {
+                nih_log!("Invalid factory preset: {err}");
+                None
+            }
+        })
+        .collect();
+
+    let mut user_presets = load_user_presets();
+    user_presets.sort_by_key(|preset| preset.name.to_lowercase());
+    presets.extend(user_presets);
+
+    presets
+}
+
+fn user_preset_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(PRESET_DIR_NAME).join(PRESET_SUBDIR_NAME))
+}
+
+fn load_user_presets() -> Vec<Preset> {
+    let Some(entries) = user_preset_dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == PRESET_EXTENSION))
+        .filter_map(|path| {
+            let json = fs::read_to_string(&path).ok()?;
+            match Preset::parse(&json, PresetSource::User) {
+                Ok(preset) => Some(Preset {
+                    path: Some(path),
+                    ..preset
+                }),
+                Err(err) => {
+                    nih_log!("Ignoring invalid preset {}: {err}", path.display());
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// The file name a new user preset starts out with. Characters that aren't allowed in file names
+/// on some platforms are replaced, so different names can end up with the same file name.
+fn user_preset_file_stem(name: &str) -> io::Result<String> {
+    let file_stem: String = name
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    if file_stem.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "empty preset name",
+        ));
+    }
+
+    Ok(file_stem)
+}
+
+/// Fails with [`io::ErrorKind::AlreadyExists`] if a user preset other than the one stored at
+/// `except` goes by `name`. Names differing only in case count as the same, like in the list.
+fn check_name_free(presets: &[Preset], name: &str, except: Option<&Path>) -> io::Result<()> {
+    let name = name.trim().to_lowercase();
+    let taken = presets.iter().any(|preset| {
+        preset.source == PresetSource::User
+            && preset.path.as_deref() != except
+            && preset.name.to_lowercase() == name
+    });
+    if taken {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "a preset with this name exists already",
+        ));
+    }
+
+    Ok(())
+}
+
+/// The `number`th candidate path for a preset file, numbers past the first are appended to the
+/// name.
+fn numbered_path(dir: &Path, file_stem: &str, number: usize) -> PathBuf {
+    match number {
+        1 => dir.join(format!("{file_stem}.{PRESET_EXTENSION}")),
+        n => dir.join(format!("{file_stem} ({n}).{PRESET_EXTENSION}")),
+    }
+}
+
+/// Writes `preset` to a new file and returns its path. This never replaces an existing file,
+/// if the name's file is taken a number is appended instead.
+fn write_new_user_preset(preset: &Preset) -> io::Result<PathBuf> {
+    let dir = user_preset_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
+    let file_stem = user_preset_file_stem(&preset.name)?;
+    let json = preset.to_json().map_err(io::Error::other)?;
+    fs::create_dir_all(&dir)?;
+
+    let mut number = 1;
+    loop {
+        let path = numbered_path(&dir, &file_stem, number);
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(mut file) => {
+                file.write_all(json.as_bytes())?;
+                return Ok(path);
+            }
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => number += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Saves the values as a new user preset. Fails with [`io::ErrorKind::AlreadyExists`] rather
+/// than replacing a preset with the same name.
+pub fn create_user_preset(
+    presets: &[Preset],
+    name: &str,
+    values: PresetValues,
+) -> io::Result<Preset> {
+    check_name_free(presets, name, None)?;
+
+    let mut preset = Preset {
+        name: name.trim().to_owned(),
+        source: PresetSource::User,
+        values,
+        path: None,
+    };
+    preset.path = Some(write_new_user_preset(&preset)?);
+    Ok(preset)
+}
+
+fn user_preset_file(preset: &Preset) -> io::Result<&Path> {
+    preset
+        .path
+        .as_deref()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a user preset"))
+}
+
+/// Writes a user preset's new values to its file.
+pub fn update_user_preset(preset: &Preset) -> io::Result<()> {
+    fs::write(
+        user_preset_file(preset)?,
+        preset.to_json().map_err(io::Error::other)?,
+    )
+}
+
+pub fn delete_user_preset(preset: &Preset) -> io::Result<()> {
+    fs::remove_file(user_preset_file(preset)?)
+}
+
+/// Moves a user preset to a new name. Like [`create_user_preset()`] this fails with
+/// [`io::ErrorKind::AlreadyExists`] if another preset has the name already.
+///
+/// The preset's file is rewritten with the new name and then renamed, so there's only ever one
+/// file for it. If the rename fails, the file keeps its old file name but has the new name inside.
+pub fn rename_user_preset(
+    presets: &[Preset],
+    preset: &Preset,
+    new_name: &str,
+) -> io::Result<Preset> {
+    let old_path = user_preset_file(preset)?;
+    check_name_free(presets, new_name, Some(old_path))?;
+    let file_stem = user_preset_file_stem(new_name)?;
+    let dir = old_path
+        .parent()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a user preset"))?;
+
+    let mut renamed = Preset {
+        name: new_name.trim().to_owned(),
+        ..preset.clone()
+    };
+    fs::write(old_path, renamed.to_json().map_err(io::Error::other)?)?;
+
+    // `fs::rename()` replaces the target, so this has to pick a free file name up front
+    let new_path = (1..)
+        .map(|number| numbered_path(dir, &file_stem, number))
+        .find(|path| path == old_path || !path.exists())
+        .expect("there is always a free file name");
+    fs::rename(old_path, &new_path)?;
+    renamed.path = Some(new_path);
+
+    Ok(renamed)
+}
@@ -0,0 +1,37 @@
+//! The colour themes of the editor. Every theme other than the default one is a CSS class on the
+//! editor's root view, with the overrides in `themes.css`.
+
+use serde::{Deserialize, Serialize};
+use vizia_plug::vizia::prelude::Data;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Data)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    /// The original light green theme.
+    #[default]
+    Green,
+    Dark,
+    HighContrast,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::Green, Theme::Dark, Theme::HighContrast];
+
+    /// The Fluent message id of the theme's name.
+    pub fn localize_key(&self) -> &'static str {
+        match self {
+            Theme::Green => "theme-green",
+            Theme::Dark => "theme-dark",
+            Theme::HighContrast => "theme-high-contrast",
+        }
+    }
+
+    /// The class added to the root view, if any.
+    pub fn class_name(&self) -> Option<&'static str> {
+        match self {
+            Theme::Green => None,
+            Theme::Dark => Some("theme-dark"),
+            Theme::HighContrast => Some("theme-high-contrast"),
+        }
+    }
+}
@@ -0,0 +1,184 @@
+//! The editor's own undo history. It records the gestures the editor sends to the host, so host
+//! automation never ends up in it.
+
+use std::collections::VecDeque;
+
+use crate::midi::MappableParam;
+
+/// The number of gestures that can be undone.
+const MAX_UNDO_ENTRIES: usize = 100;
+
+/// One undoable step, with the normalized value of every parameter before and after it.
+#[derive(Debug, Clone, Default)]
+struct UndoEntry {
+    changes: Vec<(MappableParam, f32, f32)>,
+    /// The gestures recorded into this entry that haven't ended yet.
+    gestures: Vec<GestureId>,
+}
+
+impl UndoEntry {
+    fn before(&self) -> Vec<(MappableParam, f32)> {
+        self.changes
+            .iter()
+            .map(|&(param, before, _)| (param, before))
+            .collect()
+    }
+
+    fn after(&self) -> Vec<(MappableParam, f32)> {
+        self.changes
+            .iter()
+            .map(|&(param, _, after)| (param, after))
+            .collect()
+    }
+}
+
+/// Identifies a gesture from its begin to its end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GestureId(u64);
+
+/// Gestures that overlap, like dragging the XY pad or loading a preset, become a single entry.
+#[derive(Debug, Default)]
+pub struct UndoHistory {
+    undo: VecDeque<UndoEntry>,
+    redo: Vec<UndoEntry>,
+    /// The entry being recorded while any of its gestures is open.
+    pending: Option<UndoEntry>,
+    /// Every gesture that has begun but not ended yet, with its parameter. Groups have none and
+    /// are ended by their id.
+    open_gestures: Vec<(GestureId, Option<MappableParam>)>,
+    /// Parameters whose next gesture is left out, undo and redo set the parameters through
+    /// gestures too.
+    unrecorded: Vec<MappableParam>,
+    next_gesture_id: u64,
+}
+
+impl UndoHistory {
+    /// Starts a gesture, `value` is the parameter's normalized value before it.
+    pub fn begin(&mut self, param: MappableParam, value: f32) {
+        let id = self.open(Some(param));
+        if let Some(index) = self.unrecorded.iter().position(|p| *p == param) {
+            self.unrecorded.swap_remove(index);
+            return;
+        }
+
+        let entry = self.pending.get_or_insert_with(UndoEntry::default);
+        entry.gestures.push(id);
+        if !entry.changes.iter().any(|(p, _, _)| *p == param) {
+            entry.changes.push((param, value, value));
+        }
+    }
+
+    pub fn set(&mut self, param: MappableParam, value: f32) {
+        let Some(id) = self.latest_gesture(param) else {
+            return;
+        };
+        if let Some(change) = self
+            .pending
+            .as_mut()
+            .filter(|entry| entry.gestures.contains(&id))
+            .and_then(|entry| entry.changes.iter_mut().find(|(p, _, _)| *p == param))
+        {
+            change.2 = value;
+        }
+    }
+
+    /// Ends the latest gesture on `param`.
+    pub fn end(&mut self, param: MappableParam) {
+        if let Some(id) = self.latest_gesture(param) {
+            self.close(id);
+        }
+    }
+
+    /// Keeps every gesture until the matching [`end_group()`][Self::end_group()] in a single
+    /// entry, for edits like dragging the A/B morph slider that set the parameters many times.
+    pub fn begin_group(&mut self) -> GestureId {
+        let id = self.open(None);
+        self.pending
+            .get_or_insert_with(UndoEntry::default)
+            .gestures
+            .push(id);
+
+        id
+    }
+
+    pub fn end_group(&mut self, id: GestureId) {
+        self.close(id);
+    }
+
+    fn open(&mut self, param: Option<MappableParam>) -> GestureId {
+        let id = GestureId(self.next_gesture_id);
+        self.next_gesture_id += 1;
+        self.open_gestures.push((id, param));
+
+        id
+    }
+
+    fn latest_gesture(&self, param: MappableParam) -> Option<GestureId> {
+        self.open_gestures
+            .iter()
+            .rev()
+            .find(|(_, p)| *p == Some(param))
+            .map(|&(id, _)| id)
+    }
+
+    fn close(&mut self, id: GestureId) {
+        self.open_gestures.retain(|(open_id, _)| *open_id != id);
+        let Some(entry) = self.pending.as_mut() else {
+            return;
+        };
+        entry.gestures.retain(|gesture| *gesture != id);
+        if !entry.gestures.is_empty() {
+            return;
+        }
+
+        // Clicks that didn't change anything aren't worth a step
+        if let Some(mut entry) = self.pending.take() {
+            entry.changes.retain(|(_, before, after)| before != after);
+            if !entry.changes.is_empty() {
+                if self.undo.len() == MAX_UNDO_ENTRIES {
+                    self.undo.pop_front();
+                }
+                self.undo.push_back(entry);
+                self.redo.clear();
+            }
+        }
+    }
+
+    /// Returns the values to set to undo the last step.
+    pub fn undo(&mut self) -> Option<Vec<(MappableParam, f32)>> {
+        if !self.open_gestures.is_empty() {
+            return None;
+        }
+        let entry = self.undo.pop_back()?;
+        let values = entry.before();
+        self.redo.push(entry);
+        self.skip_gestures(&values);
+        Some(values)
+    }
+
+    /// Returns the values to set to redo the last undone step.
+    pub fn redo(&mut self) -> Option<Vec<(MappableParam, f32)>> {
+        if !self.open_gestures.is_empty() {
+            return None;
+        }
+        let entry = self.redo.pop()?;
+        let values = entry.after();
+        self.undo.push_back(entry);
+        self.skip_gestures(&values);
+        Some(values)
+    }
+
+    fn skip_gestures(&mut self, values: &[(MappableParam, f32)]) {
+        for &(param, _) in values {
+            self.skip_gesture(param);
+        }
+    }
+
+    /// Leaves the next gesture on `param` out of the history, for changes the user didn't make
+    /// in the editor.
+    pub fn skip_gesture(&mut self, param: MappableParam) {
+        if !self.unrecorded.contains(&param) {
+            self.unrecorded.push(param);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! A stereo phase correlation meter, going from -1 (out of phase) to +1 (mono).
+
+use std::sync::Arc;
+use vizia_plug::vizia::{prelude::*, vg};
+
+use crate::analysis::taps::SignalTaps;
+
+pub enum CorrelationMeterEvent {
+    Update,
+    SetIntegrationTime(f32),
+}
+
+pub struct CorrelationMeter<L>
+where
+    L: Lens<Target = Arc<SignalTaps>>,
+{
+    taps: L,
+    integration_ms: f32,
+    read_pos: usize,
+    left: Vec<f32>,
+    right: Vec<f32>,
+
+    // Exponentially averaged products of the two channels
+    sum_lr: f32,
+    sum_ll: f32,
+    sum_rr: f32,
+}
+
+impl<L> CorrelationMeter<L>
+where
+    L: Lens<Target = Arc<SignalTaps>>,
+{
+    /// Creates a correlation meter for the output signal, averaged over `integration_ms`
+    /// milliseconds.
+    pub fn new<I>(cx: &mut Context, taps: L, integration_ms: I) -> Handle<'_, Self>
+    where
+        I: Lens<Target = f32>,
+    {
+        Self {
+            taps,
+            integration_ms: 300.0,
+            read_pos: 0,
+            left: Vec::new(),
+            right: Vec::new(),
+            sum_lr: 0.0,
+            sum_ll: 0.0,
+            sum_rr: 0.0,
+        }
+        .build(cx, |cx| {
+            Binding::new(cx, taps.map(|taps| taps.post.written()), |cx, _| {
+                cx.emit(CorrelationMeterEvent::Update);
+            });
+            Binding::new(cx, integration_ms, |cx, integration_ms| {
+                let integration_ms = integration_ms.get(cx);
+                cx.emit(CorrelationMeterEvent::SetIntegrationTime(integration_ms));
+            });
+        })
+    }
+
+    /// The current correlation. Silence counts as perfectly correlated, like most meters do.
+    fn correlation(&self) -> f32 {
+        let energy = (self.sum_ll * self.sum_rr).sqrt();
+        if energy <= 1e-12 {
+            1.0
+        } else {
+            (self.sum_lr / energy).clamp(-1.0, 1.0)
+        }
+    }
+}
+
+impl<L> View for CorrelationMeter<L>
+where
+    L: Lens<Target = Arc<SignalTaps>>,
+{
+    fn element(&self) -> Option<&'static str> {
+        Some("correlation-meter")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|meter_event, _| match meter_event {
+            CorrelationMeterEvent::Update => {
+                let taps = self.taps.get(cx);
+                self.left.clear();
+                self.right.clear();
+                self.read_pos =
+                    taps.post
+                        .read_stereo_since(self.read_pos, &mut self.left, &mut self.right);
+
+                let integration_samples = self.integration_ms / 1000.0 * taps.sample_rate();
+                let weight = (-1.0 / integration_samples.max(1.0)).exp();
+                for (l, r) in self.left.iter().zip(&self.right) {
+                    self.sum_lr = self.sum_lr * weight + l * r * (1.0 - weight);
+                    self.sum_ll = self.sum_ll * weight + l * l * (1.0 - weight);
+                    self.sum_rr = self.sum_rr * weight + r * r * (1.0 - weight);
+                }
+
+                if !self.left.is_empty() {
+                    cx.needs_redraw();
+                }
+            }
+            CorrelationMeterEvent::SetIntegrationTime(integration_ms) => {
+                self.integration_ms = *integration_ms;
+            }
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let background_color = cx.background_color();
+        let border_color = cx.border_color();
+        let border_width = cx.border_width();
+        let font_color = cx.font_color();
+        // Negative correlation is drawn in the outline color
+        let warning_color = cx.outline_color();
+
+        let mut paint = vg::Paint::default();
+        paint.set_color(background_color);
+        let rect = vg::Rect::from_xywh(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.draw_rect(&rect, &paint);
+
+        let bar_bounds = bounds.shrink(border_width / 2.0 + 1.0);
+        let mid_x = bar_bounds.x + bar_bounds.w / 2.0;
+        let correlation = self.correlation();
+        let value_x = mid_x + correlation * bar_bounds.w / 2.0;
+
+        // Positive correlation is fine, negative correlation will cancel out in mono
+        let mut bar_paint = vg::Paint::default();
+        if correlation >= 0.0 {
+            bar_paint.set_color(font_color);
+        } else {
+            bar_paint.set_color(warning_color);
+        }
+        bar_paint.set_alpha_f(0.6);
+        let bar = vg::Rect::from_ltrb(
+            mid_x.min(value_x),
+            bar_bounds.top(),
+            mid_x.max(value_x),
+            bar_bounds.bottom(),
+        );
+        canvas.draw_rect(&bar, &bar_paint);
+
+        // The zero line and the needle itself
+        let mut line_paint = vg::Paint::default();
+        line_paint.set_color(font_color);
+        line_paint.set_stroke_width(1.0);
+        line_paint.set_style(vg::PaintStyle::Stroke);
+        line_paint.set_alpha_f(0.3);
+        let mut center_line = vg::Path::new();
+        center_line.move_to((mid_x, bar_bounds.top()));
+        center_line.line_to((mid_x, bar_bounds.bottom()));
+        canvas.draw_path(&center_line, &line_paint);
+
+        line_paint.set_alpha_f(1.0);
+        line_paint.set_stroke_width(2.0);
+        let mut needle = vg::Path::new();
+        needle.move_to((value_x, bounds.top()));
+        needle.line_to((value_x, bounds.bottom()));
+        canvas.draw_path(&needle, &line_paint);
+
+        let mut border_paint = vg::Paint::default();
+        border_paint.set_color(border_color);
+        border_paint.set_stroke_width(border_width);
+        border_paint.set_style(vg::PaintStyle::Stroke);
+        canvas.draw_rect(&rect, &border_paint);
+    }
+}
@@ -0,0 +1,125 @@
+//! The group delay of the allpass cascade over frequency, which is where the smearing of a
+//! disperser comes from.
+
+use vizia_plug::vizia::{prelude::*, vg};
+
+use crate::analysis::allpass::{AllpassChain, ChainSettings};
+
+const MIN_FREQ: f32 = 20.0;
+const MAX_FREQ: f32 = 20000.0;
+/// The number of points the curve is evaluated at.
+const NUM_POINTS: usize = 512;
+/// The vertical scale never zooms in further than this, so a bypassed chain shows a flat line.
+const MIN_RANGE_MS: f32 = 1.0;
+
+pub enum GroupDelayViewEvent {
+    SetSettings(ChainSettings),
+}
+
+#[derive(Lens)]
+pub struct GroupDelayView {
+    /// Pairs of frequency in Hz and group delay in milliseconds.
+    curve: Vec<(f32, f32)>,
+    max_freq: f32,
+    /// The peak delay and where it happens.
+    info: String,
+}
+
+impl GroupDelayView {
+    pub fn new<L>(cx: &mut Context, settings: L) -> Handle<'_, Self>
+    where
+        L: Lens<Target = ChainSettings>,
+    {
+        Self {
+            curve: Vec::new(),
+            max_freq: MAX_FREQ,
+            info: String::new(),
+        }
+        .build(cx, |cx| {
+            Binding::new(cx, settings, |cx, settings| {
+                let settings = settings.get(cx);
+                cx.emit(GroupDelayViewEvent::SetSettings(settings));
+            });
+
+            Label::new(cx, GroupDelayView::info).class("analysis-view__info");
+        })
+    }
+}
+
+impl View for GroupDelayView {
+    fn element(&self) -> Option<&'static str> {
+        Some("group-delay-view")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|group_delay_event, _| match group_delay_event {
+            GroupDelayViewEvent::SetSettings(settings) => {
+                let chain = AllpassChain::new(*settings);
+                self.max_freq = MAX_FREQ.min(chain.nyquist() as f32);
+                self.curve = (0..NUM_POINTS)
+                    .map(|i| {
+                        let t = i as f32 / (NUM_POINTS - 1) as f32;
+                        let frequency = MIN_FREQ * (self.max_freq / MIN_FREQ).powf(t);
+                        (frequency, chain.group_delay_ms(frequency as f64) as f32)
+                    })
+                    .collect();
+
+                let (peak_freq, peak_ms) =
+                    self.curve.iter().copied().fold((0.0, 0.0), |peak, point| {
+                        if point.1 > peak.1 { point } else { peak }
+                    });
+                self.info = format!("PEAK {peak_ms:.1}MS @ {peak_freq:.0}HZ");
+                cx.needs_redraw();
+            }
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 || self.curve.is_empty() {
+            return;
+        }
+
+        let background_color = cx.background_color();
+        let font_color = cx.font_color();
+
+        let mut bg_paint = vg::Paint::default();
+        bg_paint.set_color(background_color);
+        let rect = vg::Rect::from_xywh(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.draw_rect(&rect, &bg_paint);
+
+        let range_ms = self
+            .curve
+            .iter()
+            .map(|(_, delay_ms)| *delay_ms)
+            .fold(MIN_RANGE_MS, f32::max)
+            * 1.1;
+        let x_of = |frequency: f32| {
+            bounds.x + bounds.w * (frequency / MIN_FREQ).ln() / (self.max_freq / MIN_FREQ).ln()
+        };
+        let y_of =
+            |delay_ms: f32| bounds.bottom() - bounds.h * (delay_ms / range_ms).clamp(0.0, 1.0);
+
+        let mut path = vg::Path::new();
+        path.move_to((bounds.x, bounds.bottom()));
+        for &(frequency, delay_ms) in &self.curve {
+            path.line_to((x_of(frequency), y_of(delay_ms)));
+        }
+        path.line_to((bounds.right(), bounds.bottom()));
+        path.close();
+
+        let mut fill_paint = vg::Paint::default();
+        fill_paint.set_color(font_color);
+        fill_paint.set_alpha_f(0.2);
+        fill_paint.set_style(vg::PaintStyle::Fill);
+        fill_paint.set_anti_alias(true);
+        canvas.draw_path(&path, &fill_paint);
+
+        let mut stroke_paint = vg::Paint::default();
+        stroke_paint.set_color(font_color);
+        stroke_paint.set_stroke_width(1.5);
+        stroke_paint.set_style(vg::PaintStyle::Stroke);
+        stroke_paint.set_anti_alias(true);
+        canvas.draw_path(&path, &stroke_paint);
+    }
+}
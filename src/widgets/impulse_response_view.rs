@@ -0,0 +1,128 @@
+//! The impulse response of the allpass cascade, i.e. what a single click turns into.
+
+use vizia_plug::vizia::{prelude::*, vg};
+
+use crate::analysis::allpass::{AllpassChain, ChainSettings};
+
+const MIN_LENGTH_MS: f64 = 10.0;
+const MAX_LENGTH_MS: f64 = 500.0;
+/// The response is shown for this many times the peak group delay, which leaves enough room for
+/// the chirp to ring out.
+const LENGTH_PER_DELAY: f64 = 2.0;
+
+pub enum ImpulseResponseViewEvent {
+    SetSettings(ChainSettings),
+}
+
+#[derive(Lens)]
+pub struct ImpulseResponseView {
+    response: Vec<f32>,
+    /// The length of the response, in milliseconds.
+    info: String,
+}
+
+impl ImpulseResponseView {
+    pub fn new<L>(cx: &mut Context, settings: L) -> Handle<'_, Self>
+    where
+        L: Lens<Target = ChainSettings>,
+    {
+        Self {
+            response: Vec::new(),
+            info: String::new(),
+        }
+        .build(cx, |cx| {
+            Binding::new(cx, settings, |cx, settings| {
+                let settings = settings.get(cx);
+                cx.emit(ImpulseResponseViewEvent::SetSettings(settings));
+            });
+
+            Label::new(cx, ImpulseResponseView::info).class("analysis-view__info");
+        })
+    }
+}
+
+impl View for ImpulseResponseView {
+    fn element(&self) -> Option<&'static str> {
+        Some("impulse-response-view")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|impulse_event, _| match impulse_event {
+            ImpulseResponseViewEvent::SetSettings(settings) => {
+                let chain = AllpassChain::new(*settings);
+                let length_ms = (chain.group_delay_ms(settings.frequency as f64)
+                    * LENGTH_PER_DELAY)
+                    .clamp(MIN_LENGTH_MS, MAX_LENGTH_MS);
+                let length = (length_ms / 1000.0 * chain.sample_rate).ceil() as usize;
+
+                self.response = chain.impulse_response(length);
+                self.info = format!("{length_ms:.0}MS");
+                cx.needs_redraw();
+            }
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 || self.response.is_empty() {
+            return;
+        }
+
+        let background_color = cx.background_color();
+        let font_color = cx.font_color();
+
+        let mut bg_paint = vg::Paint::default();
+        bg_paint.set_color(background_color);
+        let rect = vg::Rect::from_xywh(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.draw_rect(&rect, &bg_paint);
+
+        let center_y = bounds.y + bounds.h / 2.0;
+        let mut center_paint = vg::Paint::default();
+        center_paint.set_color(font_color);
+        center_paint.set_alpha_f(0.25);
+        center_paint.set_stroke_width(1.0);
+        center_paint.set_style(vg::PaintStyle::Stroke);
+        let mut center_line = vg::Path::new();
+        center_line.move_to((bounds.x, center_y));
+        center_line.line_to((bounds.right(), center_y));
+        canvas.draw_path(&center_line, &center_paint);
+
+        // Normalize so the chirp stays visible even when the energy is smeared over a long time
+        let peak = self
+            .response
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()))
+            .max(1e-6);
+        let scale = bounds.h / 2.0 * 0.9 / peak;
+
+        // One vertical line per pixel column from the lowest to the highest sample in it
+        let columns = bounds.w.floor().max(1.0) as usize;
+        let samples_per_column = self.response.len() as f32 / columns as f32;
+        let mut path = vg::Path::new();
+        for column in 0..columns {
+            let start = (column as f32 * samples_per_column) as usize;
+            let end = (((column + 1) as f32 * samples_per_column) as usize)
+                .max(start + 1)
+                .min(self.response.len());
+            if start >= end {
+                break;
+            }
+
+            let (min, max) = self.response[start..end]
+                .iter()
+                .fold((f32::MAX, f32::MIN), |(min, max), &sample| {
+                    (min.min(sample), max.max(sample))
+                });
+            let x = bounds.x + column as f32 + 0.5;
+            path.move_to((x, center_y - max * scale));
+            path.line_to((x, center_y - min * scale + 0.5));
+        }
+
+        let mut stroke_paint = vg::Paint::default();
+        stroke_paint.set_color(font_color);
+        stroke_paint.set_stroke_width(1.0);
+        stroke_paint.set_style(vg::PaintStyle::Stroke);
+        stroke_paint.set_anti_alias(true);
+        canvas.draw_path(&path, &stroke_paint);
+    }
+}
@@ -0,0 +1,149 @@
+//! A compact table of the input and output loudness, with the difference between the two.
+
+use nih_plug::prelude::util;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use vizia_plug::vizia::prelude::*;
+
+use crate::analysis::loudness::{LoudnessReadings, MeterReadings};
+
+pub enum LoudnessReadoutEvent {
+    Reset,
+}
+
+pub struct LoudnessReadout<L>
+where
+    L: Lens<Target = Arc<LoudnessReadings>>,
+{
+    readings: L,
+}
+
+/// Which of the three BS.1770 measurements a column shows.
+#[derive(Clone, Copy)]
+enum Measurement {
+    Momentary,
+    ShortTerm,
+    Integrated,
+}
+
+impl Measurement {
+    const ALL: [Measurement; 3] = [
+        Measurement::Momentary,
+        Measurement::ShortTerm,
+        Measurement::Integrated,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Measurement::Momentary => "M",
+            Measurement::ShortTerm => "S",
+            Measurement::Integrated => "I",
+        }
+    }
+
+    fn load(&self, readings: &MeterReadings) -> f32 {
+        match self {
+            Measurement::Momentary => readings.momentary.load(Ordering::Relaxed),
+            Measurement::ShortTerm => readings.short_term.load(Ordering::Relaxed),
+            Measurement::Integrated => readings.integrated.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn format_lufs(lufs: f32) -> String {
+    if lufs <= util::MINUS_INFINITY_DB {
+        String::from("-inf")
+    } else {
+        format!("{lufs:.1}")
+    }
+}
+
+/// The output loudness relative to the input. Only meaningful when both sides have a reading.
+fn format_delta(input: f32, output: f32) -> String {
+    if input <= util::MINUS_INFINITY_DB || output <= util::MINUS_INFINITY_DB {
+        String::from("-")
+    } else {
+        format!("{:+.1}", output - input)
+    }
+}
+
+impl<L> LoudnessReadout<L>
+where
+    L: Lens<Target = Arc<LoudnessReadings>>,
+{
+    pub fn new(cx: &mut Context, readings: L) -> Handle<'_, Self> {
+        Self { readings }.build(cx, |cx| {
+            HStack::new(cx, |cx| {
+                Label::new(cx, "LUFS").class("loudness-readout__head");
+                for measurement in Measurement::ALL {
+                    Label::new(cx, measurement.label()).class("loudness-readout__head");
+                }
+            })
+            .class("loudness-readout__row");
+
+            HStack::new(cx, |cx| {
+                Label::new(cx, Localized::new("loudness-in")).class("loudness-readout__head");
+                for measurement in Measurement::ALL {
+                    Label::new(
+                        cx,
+                        readings
+                            .map(move |readings| format_lufs(measurement.load(&readings.input))),
+                    )
+                    .class("loudness-readout__value");
+                }
+            })
+            .class("loudness-readout__row");
+
+            HStack::new(cx, |cx| {
+                Label::new(cx, Localized::new("loudness-out")).class("loudness-readout__head");
+                for measurement in Measurement::ALL {
+                    Label::new(
+                        cx,
+                        readings
+                            .map(move |readings| format_lufs(measurement.load(&readings.output))),
+                    )
+                    .class("loudness-readout__value");
+                }
+            })
+            .class("loudness-readout__row");
+
+            HStack::new(cx, |cx| {
+                Button::new(cx, |cx| Label::new(cx, Localized::new("loudness-reset")))
+                    .on_press(|ex| ex.emit(LoudnessReadoutEvent::Reset))
+                    .class("loudness-readout__reset");
+                for measurement in Measurement::ALL {
+                    Label::new(
+                        cx,
+                        readings.map(move |readings| {
+                            format_delta(
+                                measurement.load(&readings.input),
+                                measurement.load(&readings.output),
+                            )
+                        }),
+                    )
+                    .class("loudness-readout__value")
+                    .class("loudness-readout__value--delta");
+                }
+            })
+            .class("loudness-readout__row");
+        })
+    }
+}
+
+impl<L> View for LoudnessReadout<L>
+where
+    L: Lens<Target = Arc<LoudnessReadings>>,
+{
+    fn element(&self) -> Option<&'static str> {
+        Some("loudness-readout")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|readout_event, meta| match readout_event {
+            LoudnessReadoutEvent::Reset => {
+                self.readings.get(cx).request_reset();
+                meta.consume();
+            }
+        });
+    }
+}
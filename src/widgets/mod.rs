@@ -1,3 +1,15 @@
+pub mod correlation_meter;
+pub mod group_delay_view;
+pub mod impulse_response_view;
+pub mod loudness_readout;
 pub mod omg_peak_meter;
 pub mod params_knob;
+pub mod phase_response_view;
+pub mod pointer_lock;
+pub mod pole_zero_plot;
+pub mod resize_corner;
+pub mod spectrogram_view;
+pub mod spectrum_view;
+pub mod vectorscope;
 pub mod waveform_view;
+pub mod xy_pad;
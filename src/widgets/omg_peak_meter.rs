@@ -2,6 +2,7 @@
 
 use nih_plug::prelude::util;
 use std::cell::Cell;
+use std::rc::Rc;
 use std::time::Duration;
 use std::time::Instant;
 use vizia_plug::vizia::prelude::*;
@@ -20,10 +21,17 @@ const MAX_TICK: f32 = 20.0;
 /// -infinity, and at the last position we'll draw the `dBFS` string.
 const TEXT_TICKS: [i32; 6] = [-80, -60, -40, -20, 0, 12];
 
-/// A simple horizontal peak meter.
-pub struct OmgPeakMeter;
+pub enum OmgPeakMeterEvent {
+    SetHoldTime(Option<Duration>),
+}
+
+/// A simple peak meter with one bar per channel, either horizontal or vertical.
+pub struct OmgPeakMeter {
+    /// Shared with the peak lenses of every bar so the hold time can be changed at runtime.
+    hold_time: Rc<Cell<Option<Duration>>>,
+}
 
-/// The bar bit for the peak meter, manually drawn using vertical lines.
+/// The bar bit for the peak meter, manually drawn using lines across the bar.
 struct OmgPeakMeterBar<L, P>
 where
     L: Lens<Target = f32>,
@@ -31,85 +39,163 @@ where
 {
     level_dbfs: L,
     peak_dbfs: P,
+    orientation: Orientation,
 }
 
 impl OmgPeakMeter {
-    /// Creates a new [`PeakMeter`] for the given value in decibel, optionally holding the peak
-    /// value for a certain amount of time.
-    pub fn new<L>(cx: &mut Context, level_dbfs: L, hold_time: Option<Duration>) -> Handle<'_, Self>
+    /// Creates a new [`OmgPeakMeter`] with one bar for every value in decibel, optionally holding
+    /// the peak value for a certain amount of time. Pass a single lens for a mono meter, or two
+    /// `.map()`s over the same lens for a stereo one.
+    pub fn new<L>(
+        cx: &mut Context,
+        channels_dbfs: impl IntoIterator<Item = L>,
+        hold_time: Option<Duration>,
+        orientation: Orientation,
+    ) -> Handle<'_, Self>
     where
         L: Lens<Target = f32>,
     {
-        Self.build(cx, |cx| {
-            // Now for something that may be illegal under some jurisdictions. If a hold time is
-            // given, then we'll build a new lens that always gives the held peak level for the
-            // current moment in time by mutating some values captured into the mapping closure.
-            let held_peak_value_db = Cell::new(f32::MIN);
-            let last_held_peak_value: Cell<Option<Instant>> = Cell::new(None);
-            let peak_dbfs = level_dbfs.map(move |level| -> f32 {
-                match hold_time {
-                    Some(hold_time) => {
-                        let mut peak_level = held_peak_value_db.get();
-                        let peak_time = last_held_peak_value.get();
-
-                        let now = Instant::now();
-                        if *level >= peak_level
-                            || peak_time.is_none()
-                            || now > peak_time.unwrap() + hold_time
-                        {
-                            peak_level = *level;
-                            held_peak_value_db.set(peak_level);
-                            last_held_peak_value.set(Some(now));
+        let channels: Vec<L> = channels_dbfs.into_iter().collect();
+        assert!(
+            !channels.is_empty(),
+            "A peak meter needs at least one channel"
+        );
+
+        let hold_time = Rc::new(Cell::new(hold_time));
+        let redraw_lens = channels[0];
+
+        Self {
+            hold_time: hold_time.clone(),
+        }
+        .build(cx, |cx| {
+            let bars = |cx: &mut Context| {
+                for level_dbfs in channels {
+                    let hold_time = hold_time.clone();
+
+                    // Now for something that may be illegal under some jurisdictions. If a hold
+                    // time is set, then we'll build a new lens that always gives the held peak
+                    // level for the current moment in time by mutating some values captured into
+                    // the mapping closure.
+                    let held_peak_value_db = Cell::new(f32::MIN);
+                    let last_held_peak_value: Cell<Option<Instant>> = Cell::new(None);
+                    let peak_dbfs = level_dbfs.map(move |level| -> f32 {
+                        match hold_time.get() {
+                            Some(hold_time) => {
+                                let mut peak_level = held_peak_value_db.get();
+                                let peak_time = last_held_peak_value.get();
+
+                                let now = Instant::now();
+                                if *level >= peak_level
+                                    || peak_time.is_none()
+                                    || now > peak_time.unwrap() + hold_time
+                                {
+                                    peak_level = *level;
+                                    held_peak_value_db.set(peak_level);
+                                    last_held_peak_value.set(Some(now));
+                                }
+
+                                peak_level
+                            }
+                            None => util::MINUS_INFINITY_DB,
                         }
+                    });
 
-                        peak_level
+                    OmgPeakMeterBar {
+                        level_dbfs,
+                        peak_dbfs,
+                        orientation,
                     }
-                    None => util::MINUS_INFINITY_DB,
+                    .build(cx, |_| {})
+                    .class("bar");
                 }
-            });
+            };
 
-            OmgPeakMeterBar {
-                level_dbfs,
-                peak_dbfs,
+            match orientation {
+                Orientation::Horizontal => {
+                    VStack::new(cx, bars).class("bars");
+                    HStack::new(cx, |cx| build_ticks(cx, orientation)).class("ticks");
+                }
+                Orientation::Vertical => {
+                    HStack::new(cx, bars).class("bars");
+                    VStack::new(cx, |cx| build_ticks(cx, orientation)).class("ticks");
+                }
             }
-            .build(cx, |_| {})
-            .class("bar");
-
-            HStack::new(cx, |cx| {
-                for tick_db in TEXT_TICKS {
-                    let first_tick = tick_db == TEXT_TICKS[0];
-                    let last_tick = tick_db == TEXT_TICKS[TEXT_TICKS.len() - 1];
-                    VStack::new(cx, |cx| {
-                        if !last_tick {
-                            Element::new(cx).class("ticks__tick");
-                        }
+        })
+        .toggle_class("vertical", orientation == Orientation::Vertical)
+        .bind(redraw_lens, |mut handle, _| {
+            handle.needs_redraw();
+        })
+    }
+}
+
+/// The labelled ticks next to the bars. For vertical meters the loudest tick goes on top.
+fn build_ticks(cx: &mut Context, orientation: Orientation) {
+    let ticks: Vec<i32> = match orientation {
+        Orientation::Horizontal => TEXT_TICKS.to_vec(),
+        Orientation::Vertical => TEXT_TICKS.iter().rev().copied().collect(),
+    };
+
+    for (i, &tick_db) in ticks.iter().enumerate() {
+        let first_tick = tick_db == TEXT_TICKS[0];
+        let last_tick = tick_db == TEXT_TICKS[TEXT_TICKS.len() - 1];
+        let is_end = i == ticks.len() - 1;
+        let build_tick = |cx: &mut Context| {
+            if !last_tick {
+                Element::new(cx).class("ticks__tick");
+            }
+
+            if first_tick {
+                Label::new(cx, "-inf")
+                    .class("ticks__label")
+                    .class("ticks__label--inf")
+            } else if last_tick {
+                // This is only inclued in the array to make positioning this easier
+                Label::new(cx, "dBFS")
+                    .class("ticks__label")
+                    .class("ticks__label--dbfs")
+            } else {
+                Label::new(cx, &tick_db.to_string()).class("ticks__label")
+            };
+        };
 
-                        if first_tick {
-                            Label::new(cx, "-inf")
-                                .class("ticks__label")
-                                .class("ticks__label--inf")
-                        } else if last_tick {
-                            // This is only inclued in the array to make positioning this easier
-                            Label::new(cx, "dBFS")
-                                .class("ticks__label")
-                                .class("ticks__label--dbfs")
-                        } else {
-                            Label::new(cx, &tick_db.to_string()).class("ticks__label")
-                        };
-                    })
+        match orientation {
+            Orientation::Horizontal => {
+                VStack::new(cx, build_tick)
                     .width(Auto)
                     .alignment(Alignment::TopCenter);
+            }
+            Orientation::Vertical => {
+                HStack::new(cx, build_tick)
+                    .height(Auto)
+                    .alignment(Alignment::Left);
+            }
+        }
 
-                    if !last_tick {
-                        Spacer::new(cx);
-                    }
-                }
-            })
-            .class("ticks");
-        })
-        .bind(level_dbfs, |mut handle, _| {
-            handle.needs_redraw();
-        })
+        if !is_end {
+            Spacer::new(cx);
+        }
+    }
+}
+
+pub trait OmgPeakMeterHandle {
+    /// Binds the hold time to a lens, in milliseconds. A hold time of zero disables the held
+    /// peak marker.
+    fn hold_time_ms<L: Lens<Target = f32>>(self, lens: L) -> Self;
+}
+
+impl OmgPeakMeterHandle for Handle<'_, OmgPeakMeter> {
+    fn hold_time_ms<L: Lens<Target = f32>>(mut self, lens: L) -> Self {
+        let entity = self.entity();
+        Binding::new(self.context(), lens, move |cx, hold_time_ms| {
+            let hold_time_ms = hold_time_ms.get(cx);
+            let hold_time = if hold_time_ms > 0.0 {
+                Some(Duration::from_secs_f32(hold_time_ms / 1000.0))
+            } else {
+                None
+            };
+            cx.emit_to(entity, OmgPeakMeterEvent::SetHoldTime(hold_time));
+        });
+        self
     }
 }
 
@@ -117,6 +203,15 @@ impl View for OmgPeakMeter {
     fn element(&self) -> Option<&'static str> {
         Some("peak-meter")
     }
+
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|meter_event, meta| match meter_event {
+            OmgPeakMeterEvent::SetHoldTime(hold_time) => {
+                self.hold_time.set(*hold_time);
+                meta.consume();
+            }
+        });
+    }
 }
 
 impl<L, P> View for OmgPeakMeterBar<L, P>
@@ -137,6 +232,9 @@ where
         let background_color = cx.background_color();
         let border_color = cx.border_color();
         let border_width = cx.border_width();
+        // The ticks use the font color and the outline color marks clipping
+        let tick_color = cx.font_color();
+        let clip_color = cx.outline_color();
 
         let mut path = vg::Path::new();
         {
@@ -158,19 +256,45 @@ where
         canvas.draw_path(&path, &paint);
 
         // And now for the fun stuff. We'll try to not overlap the border, but we'll draw that last
-        // just in case.
+        // just in case. Horizontal bars grow to the right, vertical bars grow upwards, so for
+        // vertical bars the coordinates along the bar are measured from the bottom.
         let bar_bounds = bounds.shrink(border_width / 2.0);
-        let bar_ticks_start_x = bar_bounds.left().floor() as i32;
-        let bar_ticks_end_x = bar_bounds.right().ceil() as i32;
+        let vertical = self.orientation == Orientation::Vertical;
+        let (bar_start, bar_end) = if vertical {
+            (
+                bar_bounds.top().floor() as i32,
+                bar_bounds.bottom().ceil() as i32,
+            )
+        } else {
+            (
+                bar_bounds.left().floor() as i32,
+                bar_bounds.right().ceil() as i32,
+            )
+        };
+        let bar_length = (bar_end - bar_start) as f32;
+
+        // Draws a line across the bar at `offset` pixels from where the bar starts filling
+        let line_across = |offset: f32| {
+            let mut path = vg::Path::new();
+            if vertical {
+                let y = bar_end as f32 - offset;
+                path.move_to((bar_bounds.left(), y));
+                path.line_to((bar_bounds.right(), y));
+            } else {
+                let x = bar_start as f32 + offset;
+                path.move_to((x, bar_bounds.top()));
+                path.line_to((x, bar_bounds.bottom()));
+            }
+            path
+        };
 
         // NOTE: We'll scale this with the nearest integer DPI ratio. That way it will still look
         //       good at 2x scaling, and it won't look blurry at 1.x times scaling.
         let dpi_scale = cx.logical_to_physical(1.0).floor().max(1.0);
-        let bar_tick_coordinates = (bar_ticks_start_x..bar_ticks_end_x)
+        let bar_tick_offsets = (0..(bar_end - bar_start))
             .step_by(((TICK_WIDTH + TICK_GAP) * dpi_scale).round() as usize);
-        for tick_x in bar_tick_coordinates {
-            let tick_fraction =
-                (tick_x - bar_ticks_start_x) as f32 / (bar_ticks_end_x - bar_ticks_start_x) as f32;
+        for tick_offset in bar_tick_offsets {
+            let tick_fraction = tick_offset as f32 / bar_length;
             let tick_db = (tick_fraction * (MAX_TICK - MIN_TICK)) + MIN_TICK;
             if tick_db > level_dbfs {
                 break;
@@ -178,37 +302,34 @@ where
 
             // femtovg draws paths centered on these coordinates, so in order to be pixel perfect we
             // need to account for that. Otherwise the ticks will be 2px wide instead of 1px.
-            let mut path = vg::Path::new();
-            path.move_to((tick_x as f32 + (dpi_scale / 2.0), bar_bounds.top()));
-            path.line_to((tick_x as f32 + (dpi_scale / 2.0), bar_bounds.bottom()));
+            let path = line_across(tick_offset as f32 + (dpi_scale / 2.0));
 
-            let grayscale_color = 0.3 + ((1.0 - tick_fraction) * 0.5);
+            let tick_alpha = 0.3 + ((1.0 - tick_fraction) * 0.5);
             let mut paint = vg::Paint::default();
-            paint.set_color4f(
-                vg::Color4f::new(177.0 / 255.0, 1.0, 192.0 / 255.0, grayscale_color),
-                None,
-            );
+            if tick_db > 0.0 {
+                // Anything over 0 dBFS is clipping
+                paint.set_color(clip_color);
+            } else {
+                paint.set_color(tick_color);
+                paint.set_alpha_f(paint.alpha_f() * tick_alpha);
+            }
             paint.set_stroke_width(TICK_WIDTH * dpi_scale);
             paint.set_style(vg::PaintStyle::Stroke);
             canvas.draw_path(&path, &paint);
         }
 
         // Draw the hold peak value if the hold time option has been set
-        let db_to_x_coord = |db: f32| {
+        let db_to_offset = |db: f32| {
             let tick_fraction = (db - MIN_TICK) / (MAX_TICK - MIN_TICK);
-            bar_ticks_start_x as f32
-                + ((bar_ticks_end_x - bar_ticks_start_x) as f32 * tick_fraction).round()
+            (bar_length * tick_fraction).round()
         };
         if (MIN_TICK..MAX_TICK).contains(&peak_dbfs) {
             // femtovg draws paths centered on these coordinates, so in order to be pixel perfect we
             // need to account for that. Otherwise the ticks will be 2px wide instead of 1px.
-            let peak_x = db_to_x_coord(peak_dbfs);
-            let mut path = vg::Path::new();
-            path.move_to((peak_x + (dpi_scale / 2.0), bar_bounds.top()));
-            path.line_to((peak_x + (dpi_scale / 2.0), bar_bounds.bottom()));
+            let path = line_across(db_to_offset(peak_dbfs) + (dpi_scale / 2.0));
 
             let mut paint = vg::Paint::default();
-            paint.set_color4f(vg::Color4f::new(0.3, 0.3, 0.3, 1.0), None);
+            paint.set_color(tick_color);
             paint.set_stroke_width(TICK_WIDTH * dpi_scale);
             paint.set_style(vg::PaintStyle::Stroke);
             canvas.draw_path(&path, &paint);
@@ -222,3 +343,38 @@ where
         canvas.draw_path(&path, &paint);
     }
 }
+
+/// A clip LED and a numeric readout of the highest peak so far. Both stay put until the readout
+/// is clicked, which is left to the caller through `.on_press()`.
+pub struct PeakReadout;
+
+impl PeakReadout {
+    pub fn new<M, C>(cx: &mut Context, max_peak_dbfs: M, clipped: C) -> Handle<'_, Self>
+    where
+        M: Lens<Target = f32>,
+        C: Lens<Target = bool>,
+    {
+        Self.build(cx, |cx| {
+            Element::new(cx)
+                .class("clip-led")
+                .toggle_class("clipped", clipped);
+            Label::new(
+                cx,
+                max_peak_dbfs.map(|max_peak_dbfs| {
+                    if *max_peak_dbfs <= util::MINUS_INFINITY_DB {
+                        String::from("-inf")
+                    } else {
+                        format!("{max_peak_dbfs:+.1}")
+                    }
+                }),
+            )
+            .class("peak-readout__value");
+        })
+    }
+}
+
+impl View for PeakReadout {
+    fn element(&self) -> Option<&'static str> {
+        Some("peak-readout")
+    }
+}
@@ -1,4 +1,8 @@
-use nih_plug::prelude::Param;
+use nih_plug::nih_log;
+use nih_plug::prelude::{Param, ParamPtr};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::time::Instant;
 use vizia_plug::vizia::prelude::*;
 use vizia_plug::vizia::vg;
 use vizia_plug::vizia::vg::Point;
@@ -6,20 +10,161 @@ use vizia_plug::vizia::vg::Point;
 use vizia_plug::widgets::param_base::ParamWidgetBase;
 use vizia_plug::widgets::util::ModifiersExt;
 
-#[cfg(windows)]
-use windows::Win32::{
-    Foundation::POINT,
-    UI::WindowsAndMessaging::{GetCursorPos, SetCursorPos, ShowCursor},
-};
+use crate::widgets::pointer_lock::{PointerLock, platform_pointer_lock};
 
+/// The normalized value change per pixel of a linear drag at sensitivity 1.
 static DEFAULT_DRAG_SCALAR: f32 = 0.0042;
-static DEFAULT_WHEEL_SCALAR: f32 = 0.005;
+/// How many regular steps PageUp and PageDown move the knob by.
+const PAGE_STEPS: usize = 10;
+/// The angle a circular drag needs to cover the whole range, the same as the arc.
+const CIRCULAR_DRAG_DEGREES: f32 = 300.0;
+/// Drags faster than this many pixels per millisecond get accelerated.
+const ACCELERATION_THRESHOLD: f32 = 0.3;
+const ACCELERATION_GAIN: f32 = 1.5;
+const MAX_ACCELERATION: f32 = 4.0;
+/// How close in normalized value a drag has to come to a detent to be pulled onto it.
+const DETENT_RANGE: f32 = 0.02;
+/// Parameters with more steps than this only get a tick every few steps.
+const MAX_STEP_TICKS: usize = 24;
 
-#[derive(Debug, Clone, Copy, Default)]
+/// The range of the drag and wheel sensitivity settings.
+pub const SENSITIVITY_RANGE: Range<f32> = 0.25..4.0;
+
+/// How mouse movement turns into value changes while dragging a knob.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Data)]
+#[serde(rename_all = "kebab-case")]
+pub enum DragMode {
+    #[default]
+    Vertical,
+    Horizontal,
+    /// Up and right both increase the value.
+    VerticalHorizontal,
+    /// Follows the angle of the cursor around the knob's centre.
+    Circular,
+}
+
+impl DragMode {
+    pub const ALL: [DragMode; 4] = [
+        DragMode::Vertical,
+        DragMode::Horizontal,
+        DragMode::VerticalHorizontal,
+        DragMode::Circular,
+    ];
+
+    /// The Fluent message id of the mode's name.
+    pub fn localize_key(&self) -> &'static str {
+        match self {
+            DragMode::Vertical => "drag-vertical",
+            DragMode::Horizontal => "drag-horizontal",
+            DragMode::VerticalHorizontal => "drag-both",
+            DragMode::Circular => "drag-circular",
+        }
+    }
+}
+
+/// The user's knob preferences, stored in the [`GlobalConfig`](crate::config::GlobalConfig).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Data)]
+#[serde(default)]
+pub struct KnobSettings {
+    pub drag_mode: DragMode,
+    /// Multiplies the drag speed.
+    pub drag_sensitivity: f32,
+    /// The number of steps per mouse wheel notch.
+    pub wheel_sensitivity: f32,
+    /// Makes fast drags cover more of the range than slow ones.
+    pub acceleration: bool,
+}
+
+impl Default for KnobSettings {
+    fn default() -> Self {
+        Self {
+            drag_mode: DragMode::Vertical,
+            drag_sensitivity: 1.0,
+            wheel_sensitivity: 1.0,
+            acceleration: false,
+        }
+    }
+}
+
+/// A value the knob sticks to while dragging, like the default value.
+#[derive(Debug, Clone, PartialEq, Data)]
+pub struct Detent {
+    pub normalized_value: f32,
+    /// The Fluent message id shown while the knob rests on the detent.
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
 pub struct DragStatus {
-    drag_start_y: f32,
-    drag_start_value: f32,
-    drag_start_screen_pos: (i32, i32),
+    /// The value the drag has arrived at. This is tracked here rather than read back from the
+    /// parameter so that it doesn't depend on when the host applies the changes.
+    value: f32,
+    /// The cursor position in window coordinates at the previous move.
+    last_cursor: (f32, f32),
+    last_move: Instant,
+    /// Set if the platform's pointer lock is available. The cursor is warped back here after every
+    /// move, which makes the drag relative and unbounded.
+    drag_start_screen_pos: Option<(i32, i32)>,
+    cursor_hidden: bool,
+}
+
+impl DragStatus {
+    /// Starts a drag at `value`. With a pointer lock the cursor is hidden, and if the lock knows
+    /// where the cursor is the drag becomes relative.
+    fn new<P: PointerLock + ?Sized>(
+        value: f32,
+        cursor: (f32, f32),
+        pointer_lock: Option<&mut P>,
+    ) -> Self {
+        let cursor_hidden = pointer_lock.is_some();
+        let drag_start_screen_pos = pointer_lock.and_then(|pointer_lock| {
+            pointer_lock.hide();
+            pointer_lock.position()
+        });
+
+        Self {
+            value,
+            last_cursor: cursor,
+            last_move: Instant::now(),
+            drag_start_screen_pos,
+            cursor_hidden,
+        }
+    }
+
+    /// How far the cursor has moved since the previous move in pixels, and whether that was
+    /// measured from the drag's start, which the cursor then needs warping back to.
+    fn cursor_delta<P: PointerLock + ?Sized>(
+        &self,
+        cursor: (f32, f32),
+        pointer_lock: Option<&P>,
+    ) -> ((f32, f32), bool) {
+        let screen_pos = self
+            .drag_start_screen_pos
+            .and(pointer_lock)
+            .and_then(|pointer_lock| pointer_lock.position());
+        match (self.drag_start_screen_pos, screen_pos) {
+            (Some(start), Some(current)) => (
+                ((current.0 - start.0) as f32, (current.1 - start.1) as f32),
+                true,
+            ),
+            _ => (
+                (cursor.0 - self.last_cursor.0, cursor.1 - self.last_cursor.1),
+                false,
+            ),
+        }
+    }
+
+    fn warp_back<P: PointerLock + ?Sized>(&self, pointer_lock: &mut P) {
+        if let Some(start) = self.drag_start_screen_pos {
+            pointer_lock.warp(start);
+        }
+    }
+
+    fn finish<P: PointerLock + ?Sized>(&self, pointer_lock: Option<&mut P>) {
+        if let (true, Some(pointer_lock)) = (self.cursor_hidden, pointer_lock) {
+            pointer_lock.show();
+        }
+    }
 }
 
 #[derive(Lens)]
@@ -27,15 +172,43 @@ pub struct ParamKnob {
     param_base: ParamWidgetBase,
     text_input_active: bool,
     drag_status: Option<DragStatus>,
-    drag_scalar: f32,
-    wheel_scalar: f32,
+    settings: KnobSettings,
+    /// Wheel movement that hasn't added up to a whole step yet.
+    wheel_remainder: f32,
     centered: bool,
     dragging: bool,
+    /// The number of steps of an integer or enum parameter, `None` for continuous ones.
+    step_count: Option<usize>,
+    detents: Vec<Detent>,
+    /// The label of the detent the current drag rests on.
+    active_detent: Option<String>,
+    /// The formatted value with its unit, reported to screen readers.
+    display_value: String,
+    menu_open: bool,
+    param_ptr: ParamPtr,
+    /// Created on the first drag, since on X11 this opens a connection to the display.
+    pointer_lock: Option<Box<dyn PointerLock>>,
 }
 
 enum ParamKnobEvent {
     CancelTextInput,
     TextInput(String),
+    SetDisplayValue(String),
+    Reset,
+    EnterValue,
+    CopyValue,
+    PasteValue,
+    LearnMidiCc,
+    SetSettings(KnobSettings),
+    AddDetent(Detent),
+    /// Adds a detent with this label at the parameter's default value.
+    AddDefaultDetent(Option<String>),
+}
+
+/// Emitted by a knob's context menu for the editor to handle.
+pub enum ParamKnobMenuEvent {
+    /// Start MIDI learn for the knob's parameter.
+    LearnMidiCc(ParamPtr),
 }
 
 impl ParamKnob {
@@ -53,26 +226,50 @@ impl ParamKnob {
     {
         use self::ArcTrackHandle;
 
+        let param_base = ParamWidgetBase::new(cx, params.clone(), params_to_param);
+        let name = param_base.name().to_owned();
+        let step_count = param_base.step_count();
+        let param_ptr = params
+            .clone()
+            .map(move |params| params_to_param(params).as_ptr())
+            .get(cx);
+
         Self {
-            param_base: ParamWidgetBase::new(cx, params, params_to_param),
+            param_base,
             text_input_active: false,
             drag_status: None,
-            drag_scalar: DEFAULT_DRAG_SCALAR,
-            wheel_scalar: DEFAULT_WHEEL_SCALAR,
+            settings: KnobSettings::default(),
+            wheel_remainder: 0.0,
             centered,
             dragging: false,
+            step_count,
+            detents: Vec::new(),
+            active_detent: None,
+            display_value: String::new(),
+            menu_open: false,
+            param_ptr,
+            pointer_lock: None,
         }
         .build(
             cx,
             ParamWidgetBase::build_view(params, params_to_param, move |cx, param_data| {
                 let normalized_value_lens =
                     param_data.make_lens(|param| param.unmodulated_normalized_value());
+                // Updated whenever the host reports a modulation change, so this costs nothing on
+                // the audio thread
+                let modulated_value_lens =
+                    param_data.make_lens(|param| param.modulated_normalized_value());
                 let display_value_lens = param_data.make_lens(|param| {
                     param.normalized_value_to_string(param.unmodulated_normalized_value(), true)
                 });
 
                 let is_centered = centered;
 
+                let knob = cx.current();
+                Binding::new(cx, display_value_lens, move |cx, display_value| {
+                    cx.emit_to(knob, ParamKnobEvent::SetDisplayValue(display_value.get(cx)));
+                });
+
                 Binding::new(cx, ParamKnob::text_input_active, move |cx, active| {
                     if active.get(cx) {
                         Textbox::new(cx, display_value_lens)
@@ -92,7 +289,16 @@ impl ParamKnob {
                             .width(Stretch(1.0))
                             .height(Stretch(1.0));
                     } else {
-                        ArcTrack::new(cx, is_centered, -150.0, 150.0).value(normalized_value_lens);
+                        ArcTrack::new(cx, is_centered, -150.0, 150.0)
+                            .value(normalized_value_lens)
+                            .modulated_value(modulated_value_lens)
+                            .step_count(ParamKnob::step_count)
+                            .detents(ParamKnob::detents.map(|detents| {
+                                detents
+                                    .iter()
+                                    .map(|detent| detent.normalized_value)
+                                    .collect::<Vec<_>>()
+                            }));
                     }
                 });
 
@@ -100,13 +306,106 @@ impl ParamKnob {
                     if dragging.get(cx) {
                         HStack::new(cx, |cx| {
                             Label::new(cx, display_value_lens).class("param-value-label");
+                            Binding::new(cx, ParamKnob::active_detent, |cx, label| {
+                                if let Some(label) = label.get(cx) {
+                                    Label::new(cx, Localized::new(&label))
+                                        .class("param-value-label")
+                                        .class("param-detent-label");
+                                }
+                            });
                         })
                         .position_type(PositionType::Absolute)
                         .alignment(Alignment::TopCenter);
                     }
-                })
+                });
+
+                // TODO: The host's own entries are not part of this menu yet. Merging them in
+                // needs the CLAP context-menu extension and VST3's IComponentHandler3, and
+                // nih-plug's wrappers implement neither.
+                Binding::new(cx, ParamKnob::menu_open, |cx, open| {
+                    if open.get(cx) {
+                        VStack::new(cx, |cx| {
+                            build_menu_item(cx, "menu-reset", || ParamKnobEvent::Reset);
+                            build_menu_item(cx, "menu-enter-value", || ParamKnobEvent::EnterValue);
+                            build_menu_item(cx, "menu-copy-value", || ParamKnobEvent::CopyValue);
+                            build_menu_item(cx, "menu-paste-value", || ParamKnobEvent::PasteValue);
+                            build_menu_item(cx, "menu-learn-midi", || ParamKnobEvent::LearnMidiCc);
+                        })
+                        .class("param-knob-menu");
+                    }
+                });
+
+                // Closes the menu when clicking anywhere else, the knob only sees events from
+                // within itself
+                cx.add_listener(|knob: &mut ParamKnob, cx, event| {
+                    event.map(|window_event, _| {
+                        if let WindowEvent::MouseDown(_) = window_event {
+                            if knob.menu_open && !cx.is_over() {
+                                knob.menu_open = false;
+                            }
+                        }
+                    });
+                });
             }),
         )
+        .role(Role::Slider)
+        .name(name)
+        .navigable(true)
+    }
+
+    fn open_menu(&mut self, cx: &mut EventContext) {
+        self.menu_open = true;
+        cx.focus();
+    }
+
+    /// Sets the parameter as a single gesture, for changes that aren't part of a drag.
+    fn set_value_once(&self, cx: &mut EventContext, normalized_value: f32) {
+        self.param_base.begin_set_parameter(cx);
+        self.param_base.set_normalized_value(cx, normalized_value);
+        self.param_base.end_set_parameter(cx);
+    }
+
+    /// Rounds a drag's value to the parameter's steps and pulls it onto a nearby detent, unless
+    /// `use_detents` is off. Returns the snapped value and the label of the detent it is on.
+    fn snap_drag_value(&self, value: f32, use_detents: bool) -> (f32, Option<String>) {
+        let mut value = match self.step_count {
+            Some(steps) if steps > 0 => (value * steps as f32).round() / steps as f32,
+            _ => value,
+        };
+        if use_detents {
+            if let Some(detent) = self
+                .detents
+                .iter()
+                .filter(|detent| (detent.normalized_value - value).abs() <= DETENT_RANGE)
+                .min_by(|a, b| {
+                    (a.normalized_value - value)
+                        .abs()
+                        .total_cmp(&(b.normalized_value - value).abs())
+                })
+            {
+                value = detent.normalized_value;
+            }
+        }
+
+        let label = self
+            .detents
+            .iter()
+            .find(|detent| (detent.normalized_value - value).abs() < 1e-6)
+            .and_then(|detent| detent.label.clone());
+        (value, label)
+    }
+
+    fn step_value(&self, cx: &mut EventContext, steps: usize, up: bool) {
+        let finer = cx.modifiers().shift();
+        let mut value = self.param_base.unmodulated_normalized_value();
+        for _ in 0..steps {
+            value = if up {
+                self.param_base.next_normalized_step(value, finer)
+            } else {
+                self.param_base.previous_normalized_step(value, finer)
+            };
+        }
+        self.set_value_once(cx, value);
     }
 }
 
@@ -125,23 +424,83 @@ impl View for ParamKnob {
                 }
                 self.text_input_active = false;
                 cx.set_active(false);
+                // Hand the focus back so keyboard users can keep going from here
+                cx.focus();
                 meta.consume();
             }
             ParamKnobEvent::CancelTextInput => {
                 self.text_input_active = false;
                 cx.set_active(false);
+                cx.focus();
+                meta.consume();
+            }
+            ParamKnobEvent::SetDisplayValue(display_value) => {
+                self.display_value = display_value.clone();
+                meta.consume();
+            }
+            ParamKnobEvent::Reset => {
+                self.set_value_once(cx, self.param_base.default_normalized_value());
+                self.menu_open = false;
+                meta.consume();
+            }
+            ParamKnobEvent::EnterValue => {
+                self.menu_open = false;
+                self.text_input_active = true;
+                cx.set_active(true);
+                meta.consume();
+            }
+            ParamKnobEvent::CopyValue => {
+                if let Err(err) = cx.set_clipboard(self.display_value.clone()) {
+                    nih_log!("Failed to copy the parameter value: {err:?}");
+                }
+                self.menu_open = false;
+                meta.consume();
+            }
+            ParamKnobEvent::PasteValue => {
+                // The unit is optional, so this accepts whatever was copied from another knob
+                if let Some(val) = cx
+                    .get_clipboard()
+                    .ok()
+                    .and_then(|text| self.param_base.string_to_normalized_value(text.trim()))
+                {
+                    self.set_value_once(cx, val);
+                }
+                self.menu_open = false;
+                meta.consume();
+            }
+            ParamKnobEvent::SetSettings(settings) => {
+                self.settings = *settings;
+                meta.consume();
+            }
+            ParamKnobEvent::AddDetent(detent) => {
+                self.detents.push(detent.clone());
+                meta.consume();
+            }
+            ParamKnobEvent::AddDefaultDetent(label) => {
+                self.detents.push(Detent {
+                    normalized_value: self.param_base.default_normalized_value(),
+                    label: label.clone(),
+                });
+                meta.consume();
+            }
+            ParamKnobEvent::LearnMidiCc => {
+                cx.emit(ParamKnobMenuEvent::LearnMidiCc(self.param_ptr));
+                self.menu_open = false;
                 meta.consume();
             }
         });
 
         event.map(|window_event, meta| match window_event {
+            // Clicks on the menu items bubble up here, leave those to the menu
+            WindowEvent::MouseDown(_)
+            | WindowEvent::MouseDoubleClick(_)
+            | WindowEvent::MouseTripleClick(_)
+                if self.menu_open => {}
+
             WindowEvent::MouseDown(MouseButton::Left)
             | WindowEvent::MouseTripleClick(MouseButton::Left) => {
                 if cx.modifiers().alt() {
-                    self.param_base.begin_set_parameter(cx);
-                    self.param_base
-                        .set_normalized_value(cx, self.param_base.default_normalized_value());
-                    self.param_base.end_set_parameter(cx);
+                    self.set_value_once(cx, self.param_base.default_normalized_value());
                     meta.consume();
                 } else if cx.modifiers().command() {
                     self.text_input_active = true;
@@ -151,16 +510,26 @@ impl View for ParamKnob {
                     cx.capture();
                     cx.focus();
                     cx.set_active(true);
-                    force_hide_cursor();
                     self.dragging = true;
 
-                    let current_screen_pos = get_cursor_pos();
+                    let value = self.param_base.unmodulated_normalized_value();
+                    self.active_detent = self.snap_drag_value(value, false).1;
 
-                    self.drag_status = Some(DragStatus {
-                        drag_start_y: cx.mouse().cursor_y,
-                        drag_start_value: self.param_base.unmodulated_normalized_value(),
-                        drag_start_screen_pos: current_screen_pos,
-                    });
+                    // Circular drags need the cursor to stay visible and where it is
+                    let pointer_lock = if self.settings.drag_mode == DragMode::Circular {
+                        None
+                    } else {
+                        Some(
+                            self.pointer_lock
+                                .get_or_insert_with(platform_pointer_lock)
+                                .as_mut(),
+                        )
+                    };
+                    self.drag_status = Some(DragStatus::new(
+                        value,
+                        (cx.mouse().cursor_x, cx.mouse().cursor_y),
+                        pointer_lock,
+                    ));
 
                     self.param_base.begin_set_parameter(cx);
                     meta.consume();
@@ -168,100 +537,215 @@ impl View for ParamKnob {
             }
 
             WindowEvent::MouseDoubleClick(MouseButton::Left) => {
-                self.param_base.begin_set_parameter(cx);
-                self.param_base
-                    .set_normalized_value(cx, self.param_base.default_normalized_value());
-                self.param_base.end_set_parameter(cx);
+                self.set_value_once(cx, self.param_base.default_normalized_value());
                 meta.consume();
             }
 
             WindowEvent::MouseDown(MouseButton::Right) => {
-                self.text_input_active = true;
-                cx.set_active(true);
+                if !self.text_input_active {
+                    self.open_menu(cx);
+                }
                 meta.consume();
             }
 
             WindowEvent::MouseUp(MouseButton::Left) => {
-                if self.drag_status.is_some() {
-                    self.drag_status = None;
-
+                if let Some(status) = self.drag_status.take() {
                     // cx.set_cursor_icon(CursorIcon::Default);
                     // cx.set_cursor_grab(CursorGrabMode::Ungrab);
-                    force_show_cursor();
+                    status.finish(self.pointer_lock.as_deref_mut());
 
                     cx.release();
                     cx.set_active(false);
                     self.param_base.end_set_parameter(cx);
                     meta.consume();
                     self.dragging = false;
+                    self.active_detent = None;
                 }
             }
 
-            WindowEvent::MouseMove(_, _y) => {
-                if let Some(status) = self.drag_status {
-                    let (dy, current_screen_pos) = get_drag_delta(cx, status);
+            WindowEvent::MouseMove(cursor_x, cursor_y) => {
+                if let Some(mut status) = self.drag_status {
+                    let cursor = (*cursor_x, *cursor_y);
+                    let now = Instant::now();
+                    let ((dx, dy), locked) =
+                        status.cursor_delta(cursor, self.pointer_lock.as_deref());
 
-                    if dy != 0.0 {
-                        let mut value_delta = dy * self.drag_scalar;
-                        if cx.modifiers().shift() {
-                            value_delta *= 0.1;
+                    let mut value_delta = match self.settings.drag_mode {
+                        DragMode::Circular => {
+                            let bounds = cx.bounds();
+                            let center = (bounds.x + bounds.w / 2.0, bounds.y + bounds.h / 2.0);
+                            let angle = |(x, y): (f32, f32)| (y - center.1).atan2(x - center.0);
+                            // Wrapped so crossing the left side doesn't jump a full turn
+                            let mut delta = angle(cursor) - angle(status.last_cursor);
+                            if delta > std::f32::consts::PI {
+                                delta -= std::f32::consts::TAU;
+                            } else if delta < -std::f32::consts::PI {
+                                delta += std::f32::consts::TAU;
+                            }
+                            delta.to_degrees() / CIRCULAR_DRAG_DEGREES
                         }
+                        mode => {
+                            let pixels = match mode {
+                                DragMode::Horizontal => dx,
+                                DragMode::VerticalHorizontal => dx - dy,
+                                _ => -dy,
+                            };
+                            let mut delta = pixels * DEFAULT_DRAG_SCALAR;
+                            if self.settings.acceleration {
+                                let elapsed_ms = (now - status.last_move).as_secs_f32() * 1000.0;
+                                let speed = dx.hypot(dy) / elapsed_ms.max(1.0);
+                                delta *= (1.0
+                                    + (speed - ACCELERATION_THRESHOLD).max(0.0)
+                                        * ACCELERATION_GAIN)
+                                    .min(MAX_ACCELERATION);
+                            }
+                            delta
+                        }
+                    };
+                    value_delta *= self.settings.drag_sensitivity;
+                    if cx.modifiers().shift() {
+                        value_delta *= 0.1;
+                    }
 
-                        #[cfg(windows)]
-                        let new_val = {
-                            let current_val = self.param_base.unmodulated_normalized_value();
-                            (current_val + value_delta).clamp(0.0, 1.0)
-                        };
-
-                        #[cfg(not(windows))]
-                        let new_val = (status.drag_start_value + value_delta).clamp(0.0, 1.0);
-
-                        self.param_base.set_normalized_value(cx, new_val);
+                    if value_delta != 0.0 {
+                        // The drag keeps its own unsnapped value, so it can move off a detent
+                        // again. Fine adjustments skip the detents.
+                        status.value = (status.value + value_delta).clamp(0.0, 1.0);
+                        let (value, detent) =
+                            self.snap_drag_value(status.value, !cx.modifiers().shift());
+                        self.active_detent = detent;
+                        self.param_base.set_normalized_value(cx, value);
 
                         // 强行拉回
-                        if current_screen_pos.is_some() {
-                            set_cursor_pos(status.drag_start_screen_pos);
+                        if let (true, Some(pointer_lock)) = (locked, &mut self.pointer_lock) {
+                            status.warp_back(pointer_lock.as_mut());
                         }
                     }
+
+                    status.last_cursor = cursor;
+                    status.last_move = now;
+                    self.drag_status = Some(status);
                     meta.consume();
                 }
             }
 
             WindowEvent::MouseScroll(_, y) => {
                 if *y != 0.0 && self.drag_status.is_none() {
-                    self.param_base.begin_set_parameter(cx);
+                    // Trackpads and low sensitivities send less than a step at a time
+                    self.wheel_remainder += *y * self.settings.wheel_sensitivity;
+                    let steps = self.wheel_remainder.trunc();
+                    if steps != 0.0 {
+                        self.wheel_remainder -= steps;
+                        self.step_value(cx, steps.abs() as usize, steps > 0.0);
+                    }
+                    meta.consume();
+                }
+            }
 
-                    let use_finer_steps = cx.modifiers().shift();
-                    let mut current_value = self.param_base.unmodulated_normalized_value();
+            WindowEvent::KeyDown(Code::Escape, _) if self.menu_open => {
+                self.menu_open = false;
+                meta.consume();
+            }
 
-                    if *y > 0.0 {
-                        current_value = self
-                            .param_base
-                            .next_normalized_step(current_value, use_finer_steps);
-                    } else {
-                        current_value = self
-                            .param_base
-                            .previous_normalized_step(current_value, use_finer_steps);
+            // The text entry handles its own keys
+            WindowEvent::KeyDown(code, _)
+                if !self.text_input_active && self.drag_status.is_none() =>
+            {
+                match code {
+                    Code::ArrowUp | Code::ArrowRight => self.step_value(cx, 1, true),
+                    Code::ArrowDown | Code::ArrowLeft => self.step_value(cx, 1, false),
+                    Code::PageUp => self.step_value(cx, PAGE_STEPS, true),
+                    Code::PageDown => self.step_value(cx, PAGE_STEPS, false),
+                    Code::Home => self.set_value_once(cx, 0.0),
+                    Code::End => self.set_value_once(cx, 1.0),
+                    Code::Delete | Code::Backspace => {
+                        self.set_value_once(cx, self.param_base.default_normalized_value())
                     }
-
-                    self.param_base.set_normalized_value(cx, current_value);
-                    self.param_base.end_set_parameter(cx);
-                    meta.consume();
+                    Code::Enter | Code::NumpadEnter => {
+                        self.menu_open = false;
+                        self.text_input_active = true;
+                        cx.set_active(true);
+                    }
+                    Code::ContextMenu => self.open_menu(cx),
+                    Code::F10 if cx.modifiers().shift() => self.open_menu(cx),
+                    _ => return,
                 }
+                meta.consume();
             }
             _ => {}
         });
     }
+
+    fn accessibility(&self, _cx: &mut AccessContext, node: &mut AccessNode) {
+        node.set_min_numeric_value(0.0);
+        node.set_max_numeric_value(1.0);
+        node.set_numeric_value(self.param_base.unmodulated_normalized_value() as f64);
+        node.set_value(self.display_value.clone());
+    }
+}
+
+pub trait ParamKnobHandle {
+    /// Applies the user's knob preferences, see [`KnobSettings`].
+    fn settings<L: Lens<Target = KnobSettings>>(self, lens: L) -> Self;
+    /// Adds a detent at a normalized value, with an optional Fluent message id as its label.
+    fn detent(self, normalized_value: f32, label: Option<&str>) -> Self;
+    /// Adds a detent at the parameter's default value.
+    fn default_detent(self, label: Option<&str>) -> Self;
+}
+
+impl ParamKnobHandle for Handle<'_, ParamKnob> {
+    fn settings<L: Lens<Target = KnobSettings>>(mut self, lens: L) -> Self {
+        let entity = self.entity();
+        Binding::new(self.context(), lens, move |cx, settings| {
+            cx.emit_to(entity, ParamKnobEvent::SetSettings(settings.get(cx)));
+        });
+        self
+    }
+
+    fn detent(mut self, normalized_value: f32, label: Option<&str>) -> Self {
+        let entity = self.entity();
+        let detent = Detent {
+            normalized_value,
+            label: label.map(str::to_owned),
+        };
+        self.context()
+            .emit_to(entity, ParamKnobEvent::AddDetent(detent));
+        self
+    }
+
+    fn default_detent(mut self, label: Option<&str>) -> Self {
+        let entity = self.entity();
+        self.context().emit_to(
+            entity,
+            ParamKnobEvent::AddDefaultDetent(label.map(str::to_owned)),
+        );
+        self
+    }
+}
+
+fn build_menu_item(cx: &mut Context, key: &'static str, event: fn() -> ParamKnobEvent) {
+    Button::new(cx, move |cx| Label::new(cx, Localized::new(key)))
+        .on_press(move |ex| ex.emit(event()))
+        .class("param-knob-menu__item");
 }
 
 pub enum ArcTrackEvent {
     SetValue(f32),
+    SetModulatedValue(f32),
+    SetStepCount(Option<usize>),
+    SetDetents(Vec<f32>),
 }
 
 pub struct ArcTrack {
     angle_start: f32,
     angle_end: f32,
     normalized_value: f32,
+    /// The value after modulation, this equals `normalized_value` when nothing modulates it.
+    modulated_value: f32,
+    /// Draws a tick at every step, or every few steps, if set.
+    step_count: Option<usize>,
+    /// The normalized values of the detents, drawn as longer ticks.
+    detents: Vec<f32>,
     center: bool,
 }
 
@@ -276,6 +760,9 @@ impl ArcTrack {
             angle_start,
             angle_end,
             normalized_value: 0.0,
+            modulated_value: 0.0,
+            step_count: None,
+            detents: Vec::new(),
             center,
         }
         .build(cx, |_| {})
@@ -284,6 +771,9 @@ impl ArcTrack {
 
 pub trait ArcTrackHandle {
     fn value<L: Lens<Target = f32>>(self, lens: L) -> Self;
+    fn modulated_value<L: Lens<Target = f32>>(self, lens: L) -> Self;
+    fn step_count<L: Lens<Target = Option<usize>>>(self, lens: L) -> Self;
+    fn detents<L: Lens<Target = Vec<f32>>>(self, lens: L) -> Self;
 }
 
 impl ArcTrackHandle for Handle<'_, ArcTrack> {
@@ -294,6 +784,30 @@ impl ArcTrackHandle for Handle<'_, ArcTrack> {
         });
         self
     }
+
+    fn modulated_value<L: Lens<Target = f32>>(mut self, lens: L) -> Self {
+        let entity = self.entity();
+        Binding::new(self.context(), lens, move |cx, value| {
+            cx.emit_to(entity, ArcTrackEvent::SetModulatedValue(value.get(cx)));
+        });
+        self
+    }
+
+    fn step_count<L: Lens<Target = Option<usize>>>(mut self, lens: L) -> Self {
+        let entity = self.entity();
+        Binding::new(self.context(), lens, move |cx, step_count| {
+            cx.emit_to(entity, ArcTrackEvent::SetStepCount(step_count.get(cx)));
+        });
+        self
+    }
+
+    fn detents<L: Lens<Target = Vec<f32>>>(mut self, lens: L) -> Self {
+        let entity = self.entity();
+        Binding::new(self.context(), lens, move |cx, detents| {
+            cx.emit_to(entity, ArcTrackEvent::SetDetents(detents.get(cx)));
+        });
+        self
+    }
 }
 
 impl View for ArcTrack {
@@ -307,13 +821,29 @@ impl View for ArcTrack {
                 self.normalized_value = *val;
                 cx.needs_redraw();
             }
+            ArcTrackEvent::SetModulatedValue(val) => {
+                self.modulated_value = *val;
+                cx.needs_redraw();
+            }
+            ArcTrackEvent::SetStepCount(step_count) => {
+                self.step_count = *step_count;
+                cx.needs_redraw();
+            }
+            ArcTrackEvent::SetDetents(detents) => {
+                self.detents = detents.clone();
+                cx.needs_redraw();
+            }
         });
     }
 
     fn draw(&self, cx: &mut DrawContext, canvas: &vg::Canvas) {
-        let foreground_color = Color::rgba(0, 255, 60, 50);
+        // The face, the active arc, the tick and the modulation come from the stylesheet so themes
+        // can change them
+        let face_color = cx.background_color();
+        let foreground_color = cx.font_color();
         let background_color = Color::transparent();
-        let tick_color = Color::black();
+        let tick_color = cx.border_color();
+        let modulation_color = cx.outline_color();
         let bounds = cx.bounds();
 
         if bounds.w == 0.0 || bounds.h == 0.0 {
@@ -339,7 +869,7 @@ impl View for ArcTrack {
         );
 
         let mut paint = vg::Paint::default();
-        paint.set_color(Color::rgb(241, 255, 243));
+        paint.set_color(face_color);
         paint.set_style(vg::PaintStyle::Fill);
         canvas.draw_circle(Point::new(center_x, center_y), draw_radius, &paint);
 
@@ -360,6 +890,44 @@ impl View for ArcTrack {
         paint_fg.set_style(vg::PaintStyle::Stroke);
         paint_fg.set_anti_alias(true);
 
+        // draw the steps and detents as ticks inside the ring, faint for steps and solid for
+        // detents
+        let mut paint_marks = vg::Paint::default();
+        paint_marks.set_color(tick_color);
+        paint_marks.set_stroke_width(1.0);
+        paint_marks.set_stroke_cap(vg::PaintCap::Butt);
+        paint_marks.set_style(vg::PaintStyle::Stroke);
+        paint_marks.set_anti_alias(true);
+        let marks_outer_radius = radius - stroke_width * 1.7;
+
+        if let Some(steps) = self.step_count.filter(|&steps| steps > 0) {
+            paint_marks.set_alpha_f(paint_marks.alpha_f() * 0.35);
+            let stride = steps.div_ceil(MAX_STEP_TICKS);
+            for step in (0..steps).step_by(stride).chain(std::iter::once(steps)) {
+                let angle_deg = start_angle_deg + step as f32 / steps as f32 * sweep_angle_deg;
+                draw_radial_line(
+                    canvas,
+                    (center_x, center_y),
+                    angle_deg,
+                    (marks_outer_radius - stroke_width * 0.6, marks_outer_radius),
+                    &paint_marks,
+                );
+            }
+        }
+
+        paint_marks.set_color(tick_color);
+        paint_marks.set_stroke_width(2.0);
+        for detent in &self.detents {
+            let angle_deg = start_angle_deg + detent * sweep_angle_deg;
+            draw_radial_line(
+                canvas,
+                (center_x, center_y),
+                angle_deg,
+                (marks_outer_radius - stroke_width * 1.2, marks_outer_radius),
+                &paint_marks,
+            );
+        }
+
         let value = self.normalized_value;
 
         if self.center {
@@ -378,6 +946,39 @@ impl View for ArcTrack {
             canvas.draw_arc(&oval, start_angle_deg, current_sweep_deg, false, &paint_fg);
         }
 
+        // draw the modulation depth on the main ring, and the modulated value as a thinner ring
+        // just inside it
+        let modulated_value = self.modulated_value;
+        if (modulated_value - value).abs() > 1e-4 {
+            let mut paint_mod = vg::Paint::default();
+            paint_mod.set_color(modulation_color);
+            paint_mod.set_stroke_width(stroke_width);
+            paint_mod.set_stroke_cap(vg::PaintCap::Butt);
+            paint_mod.set_style(vg::PaintStyle::Stroke);
+            paint_mod.set_anti_alias(true);
+
+            let depth_start_deg = start_angle_deg + value * sweep_angle_deg;
+            let depth_sweep_deg = (modulated_value - value) * sweep_angle_deg;
+            canvas.draw_arc(&oval, depth_start_deg, depth_sweep_deg, false, &paint_mod);
+
+            let inner_stroke_width = stroke_width * 0.4;
+            let inner_radius = draw_radius - stroke_width / 2.0 - inner_stroke_width;
+            let inner_oval = vg::Rect::new(
+                center_x - inner_radius,
+                center_y - inner_radius,
+                center_x + inner_radius,
+                center_y + inner_radius,
+            );
+            paint_mod.set_stroke_width(inner_stroke_width);
+            canvas.draw_arc(
+                &inner_oval,
+                start_angle_deg,
+                modulated_value * sweep_angle_deg,
+                false,
+                &paint_mod,
+            );
+        }
+
         // draw indicator tick
         let current_angle_deg = start_angle_deg + value * sweep_angle_deg;
         let current_angle_rad = current_angle_deg.to_radians();
@@ -403,50 +1004,72 @@ impl View for ArcTrack {
     }
 }
 
-fn force_show_cursor() {
-    #[cfg(windows)]
-    while unsafe { ShowCursor(true) } < 0 {}
+fn draw_radial_line(
+    canvas: &vg::Canvas,
+    center: (f32, f32),
+    angle_deg: f32,
+    (inner_radius, outer_radius): (f32, f32),
+    paint: &vg::Paint,
+) {
+    let (sin, cos) = angle_deg.to_radians().sin_cos();
+    let mut path = vg::Path::new();
+    path.move_to((center.0 + cos * inner_radius, center.1 + sin * inner_radius));
+    path.line_to((center.0 + cos * outer_radius, center.1 + sin * outer_radius));
+    canvas.draw_path(&path, paint);
 }
 
-fn force_hide_cursor() {
-    #[cfg(windows)]
-    while unsafe { ShowCursor(false) } > 0 {}
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::widgets::pointer_lock::recording::RecordingPointerLock;
 
-#[cfg(windows)]
-fn get_cursor_pos() -> (i32, i32) {
-    let mut pos = POINT::default();
-    unsafe {
-        GetCursorPos(&mut pos).unwrap();
-    }
-    (pos.x, pos.y)
-}
+    #[test]
+    fn locked_drag_measures_from_start_and_warps_back() {
+        let mut pointer_lock = RecordingPointerLock::new(&[(500, 300), (500, 290), (512, 305)]);
+        let mut status = DragStatus::new(0.5, (20.0, 20.0), Some(&mut pointer_lock));
+        assert_eq!(pointer_lock.hide_calls, 1);
+        assert_eq!(status.drag_start_screen_pos, Some((500, 300)));
 
-#[cfg(not(windows))]
-fn get_cursor_pos() -> (i32, i32) {
-    (0, 0)
-}
+        // The window cursor is ignored while the pointer is locked
+        let (delta, locked) = status.cursor_delta((0.0, 0.0), Some(&pointer_lock));
+        assert_eq!((delta, locked), ((0.0, -10.0), true));
+        status.warp_back(&mut pointer_lock);
+        status.last_cursor = (0.0, 0.0);
+
+        // Every move is measured from the start again, since the cursor was warped back there
+        let (delta, locked) = status.cursor_delta((0.0, 0.0), Some(&pointer_lock));
+        assert_eq!((delta, locked), ((12.0, 5.0), true));
+        status.warp_back(&mut pointer_lock);
+        assert_eq!(pointer_lock.warps, vec![(500, 300), (500, 300)]);
 
-#[cfg(windows)]
-fn set_cursor_pos(pos: (i32, i32)) {
-    unsafe {
-        SetCursorPos(pos.0, pos.1).unwrap();
+        status.finish(Some(&mut pointer_lock));
+        assert_eq!(pointer_lock.show_calls, 1);
     }
-}
 
-#[cfg(not(windows))]
-fn set_cursor_pos(_pos: (i32, i32)) {}
+    #[test]
+    fn drag_without_cursor_position_uses_window_coordinates() {
+        let mut pointer_lock = RecordingPointerLock::new(&[]);
+        let status = DragStatus::new(0.5, (20.0, 20.0), Some(&mut pointer_lock));
+        assert_eq!(status.drag_start_screen_pos, None);
 
-fn get_drag_delta(cx: &EventContext, status: DragStatus) -> (f32, Option<(i32, i32)>) {
-    #[cfg(windows)]
-    {
-        let current_screen_pos = get_cursor_pos();
-        let dy = (status.drag_start_screen_pos.1 - current_screen_pos.1) as f32;
-        (dy, Some(current_screen_pos))
+        let (delta, locked) = status.cursor_delta((25.0, 14.0), Some(&pointer_lock));
+        assert_eq!((delta, locked), ((5.0, -6.0), false));
+
+        // The cursor is still hidden for the drag and has to come back
+        status.finish(Some(&mut pointer_lock));
+        assert_eq!(pointer_lock.hide_calls, 1);
+        assert_eq!(pointer_lock.show_calls, 1);
+        assert!(pointer_lock.warps.is_empty());
     }
-    #[cfg(not(windows))]
-    {
-        let dy = status.drag_start_y - cx.mouse().cursor_y;
-        (dy, None)
+
+    #[test]
+    fn circular_drag_leaves_the_cursor_alone() {
+        let status = DragStatus::new::<RecordingPointerLock>(0.5, (20.0, 20.0), None);
+        let (delta, locked) = status.cursor_delta::<RecordingPointerLock>((21.0, 22.0), None);
+        assert_eq!((delta, locked), ((1.0, 2.0), false));
+
+        let mut pointer_lock = RecordingPointerLock::new(&[]);
+        status.finish(Some(&mut pointer_lock));
+        assert_eq!(pointer_lock.show_calls, 0);
     }
 }
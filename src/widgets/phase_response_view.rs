@@ -0,0 +1,406 @@
+//! The unwrapped phase and the magnitude of the allpass cascade, computed from the coefficients and
+//! overlaid with the transfer function measured between the pre and post taps.
+
+use realfft::num_complex::Complex;
+use realfft::{RealFftPlanner, RealToComplex};
+use std::f32::consts::PI;
+use std::sync::Arc;
+use vizia_plug::vizia::{prelude::*, vg};
+
+use crate::analysis::allpass::{AllpassChain, ChainSettings};
+use crate::analysis::taps::SignalTaps;
+
+/// Large enough to capture the group delay of most settings. Anything delayed by more than the
+/// window shows up as a drop in the measured magnitude.
+const FFT_SIZE: usize = 8192;
+const HOP_SIZE: usize = 2048;
+/// How much every new frame contributes to the averaged spectra.
+const AVERAGING: f64 = 0.2;
+/// Bins where the pre signal is quieter than this relative to the loudest bin are not drawn,
+/// since there is nothing to measure there.
+const MIN_RELATIVE_POWER: f64 = 1e-6;
+
+const MIN_FREQ: f32 = 20.0;
+const MAX_FREQ: f32 = 20000.0;
+/// The number of points the analytic curves are evaluated at.
+const NUM_POINTS: usize = 256;
+/// The magnitude pane shows this many decibels above and below 0 dB.
+const MAGNITUDE_RANGE_DB: f32 = 6.0;
+/// The share of the height used by the phase pane. The magnitude pane gets the rest.
+const PHASE_PANE_RATIO: f32 = 0.65;
+
+pub enum PhaseResponseViewEvent {
+    Update,
+    SetSettings(ChainSettings),
+}
+
+/// A single point on one of the curves.
+#[derive(Clone, Copy)]
+struct ResponsePoint {
+    frequency: f32,
+    /// Unwrapped, in radians.
+    phase: f32,
+    magnitude_db: f32,
+}
+
+/// Estimates the transfer function from the pre to the post signal by averaging the cross
+/// spectrum and dividing it by the averaged power spectrum of the input.
+struct Measurement {
+    read_pos: usize,
+    pending_pre: Vec<f32>,
+    pending_post: Vec<f32>,
+    window: Vec<f32>,
+    fft: Arc<dyn RealToComplex<f32>>,
+    fft_input: Vec<f32>,
+    pre_spectrum: Vec<Complex<f32>>,
+    post_spectrum: Vec<Complex<f32>>,
+    cross_power: Vec<Complex<f64>>,
+    input_power: Vec<f64>,
+    has_data: bool,
+}
+
+impl Measurement {
+    fn new() -> Self {
+        let fft = RealFftPlanner::new().plan_fft_forward(FFT_SIZE);
+        let window = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / FFT_SIZE as f32).cos())
+            .collect();
+        let num_bins = FFT_SIZE / 2 + 1;
+
+        Self {
+            read_pos: 0,
+            pending_pre: Vec::with_capacity(FFT_SIZE * 2),
+            pending_post: Vec::with_capacity(FFT_SIZE * 2),
+            window,
+            fft_input: fft.make_input_vec(),
+            pre_spectrum: fft.make_output_vec(),
+            post_spectrum: fft.make_output_vec(),
+            fft,
+            cross_power: vec![Complex::new(0.0, 0.0); num_bins],
+            input_power: vec![0.0; num_bins],
+            has_data: false,
+        }
+    }
+
+    /// Throws away the averages, for when the chain changed and the old measurement is stale.
+    fn reset(&mut self) {
+        self.cross_power.fill(Complex::new(0.0, 0.0));
+        self.input_power.fill(0.0);
+        self.has_data = false;
+    }
+
+    fn transform(
+        fft: &Arc<dyn RealToComplex<f32>>,
+        window: &[f32],
+        input: &mut [f32],
+        samples: &[f32],
+        output: &mut [Complex<f32>],
+    ) -> bool {
+        for ((input, sample), window) in input.iter_mut().zip(samples).zip(window) {
+            *input = sample * window;
+        }
+
+        fft.process(input, output).is_ok()
+    }
+
+    /// Pulls new samples from the taps and folds every full frame into the averages.
+    fn update(&mut self, taps: &SignalTaps) -> bool {
+        self.read_pos =
+            taps.read_pre_post_since(self.read_pos, &mut self.pending_pre, &mut self.pending_post);
+
+        // Only the most recent frames matter, the rest would only slow down the GUI
+        let max_pending = FFT_SIZE + HOP_SIZE * 4;
+        if self.pending_pre.len() > max_pending {
+            let excess = self.pending_pre.len() - max_pending;
+            self.pending_pre.drain(..excess);
+            self.pending_post.drain(..excess);
+        }
+
+        let mut added = false;
+        while self.pending_pre.len() >= FFT_SIZE {
+            let pre_ok = Self::transform(
+                &self.fft,
+                &self.window,
+                &mut self.fft_input,
+                &self.pending_pre[..FFT_SIZE],
+                &mut self.pre_spectrum,
+            );
+            let post_ok = Self::transform(
+                &self.fft,
+                &self.window,
+                &mut self.fft_input,
+                &self.pending_post[..FFT_SIZE],
+                &mut self.post_spectrum,
+            );
+
+            if pre_ok && post_ok {
+                let weight = if self.has_data { AVERAGING } else { 1.0 };
+                for (((cross, power), pre), post) in self
+                    .cross_power
+                    .iter_mut()
+                    .zip(self.input_power.iter_mut())
+                    .zip(&self.pre_spectrum)
+                    .zip(&self.post_spectrum)
+                {
+                    let pre = Complex::new(pre.re as f64, pre.im as f64);
+                    let post = Complex::new(post.re as f64, post.im as f64);
+                    *cross += (post * pre.conj() - *cross) * weight;
+                    *power += (pre.norm_sqr() - *power) * weight;
+                }
+                self.has_data = true;
+                added = true;
+            }
+
+            self.pending_pre.drain(..HOP_SIZE);
+            self.pending_post.drain(..HOP_SIZE);
+        }
+
+        added
+    }
+
+    /// Turns the averages into phase and magnitude per bin. Bins without enough input energy are
+    /// left out. The measured phase is only known up to a multiple of 2π, so every contiguous run
+    /// of bins is aligned to the analytic phase of `chain` at its first bin.
+    fn response(&self, chain: &AllpassChain, sample_rate: f32) -> Vec<Option<ResponsePoint>> {
+        let mut points = vec![None; self.input_power.len()];
+        if !self.has_data {
+            return points;
+        }
+
+        let max_power = self.input_power.iter().copied().fold(0.0, f64::max);
+        let min_power = max_power * MIN_RELATIVE_POWER;
+        if min_power <= 0.0 {
+            return points;
+        }
+
+        let bin_width = sample_rate / FFT_SIZE as f32;
+        let mut previous: Option<(f32, f32)> = None;
+        for (bin, (cross, power)) in self.cross_power.iter().zip(&self.input_power).enumerate() {
+            let frequency = bin as f32 * bin_width;
+            if frequency < MIN_FREQ || *power < min_power {
+                previous = None;
+                continue;
+            }
+
+            let transfer = *cross / *power;
+            let wrapped = transfer.arg() as f32;
+            let phase = match previous {
+                Some((previous_wrapped, previous_phase)) => {
+                    let mut delta = wrapped - previous_wrapped;
+                    delta -= (delta / (2.0 * PI)).round() * 2.0 * PI;
+                    previous_phase + delta
+                }
+                None => {
+                    let target = chain.phase(frequency as f64) as f32;
+                    wrapped + ((target - wrapped) / (2.0 * PI)).round() * 2.0 * PI
+                }
+            };
+            previous = Some((wrapped, phase));
+
+            points[bin] = Some(ResponsePoint {
+                frequency,
+                phase,
+                magnitude_db: 20.0 * (transfer.norm().max(1e-6) as f32).log10(),
+            });
+        }
+
+        points
+    }
+}
+
+pub struct PhaseResponseView<L>
+where
+    L: Lens<Target = Arc<SignalTaps>>,
+{
+    taps: L,
+    chain: AllpassChain,
+    analytic: Vec<ResponsePoint>,
+    measurement: Measurement,
+    measured: Vec<Option<ResponsePoint>>,
+}
+
+impl<L> PhaseResponseView<L>
+where
+    L: Lens<Target = Arc<SignalTaps>>,
+{
+    pub fn new<S>(cx: &mut Context, taps: L, settings: S) -> Handle<'_, Self>
+    where
+        S: Lens<Target = ChainSettings>,
+    {
+        Self {
+            taps,
+            chain: AllpassChain::default(),
+            analytic: Vec::new(),
+            measurement: Measurement::new(),
+            measured: Vec::new(),
+        }
+        .build(cx, |cx| {
+            Binding::new(cx, taps.map(|taps| taps.post.written()), |cx, _| {
+                cx.emit(PhaseResponseViewEvent::Update);
+            });
+            Binding::new(cx, settings, |cx, settings| {
+                let settings = settings.get(cx);
+                cx.emit(PhaseResponseViewEvent::SetSettings(settings));
+            });
+        })
+    }
+
+    fn compute_analytic(&mut self) {
+        let max_freq = MAX_FREQ.min(self.chain.nyquist() as f32);
+        self.analytic = (0..NUM_POINTS)
+            .map(|i| {
+                let t = i as f32 / (NUM_POINTS - 1) as f32;
+                let frequency = MIN_FREQ * (max_freq / MIN_FREQ).powf(t);
+                ResponsePoint {
+                    frequency,
+                    phase: self.chain.phase(frequency as f64) as f32,
+                    magnitude_db: self.chain.magnitude_db(frequency as f64) as f32,
+                }
+            })
+            .collect();
+    }
+}
+
+/// Strokes a curve through `points`, starting a new segment after every gap.
+fn stroke_curve(
+    canvas: &Canvas,
+    points: impl Iterator<Item = Option<(f32, f32)>>,
+    paint: &vg::Paint,
+) {
+    let mut path = vg::Path::new();
+    let mut pen_down = false;
+    for point in points {
+        match point {
+            Some((x, y)) if pen_down => {
+                path.line_to((x, y));
+            }
+            Some((x, y)) => {
+                path.move_to((x, y));
+                pen_down = true;
+            }
+            None => pen_down = false,
+        }
+    }
+    canvas.draw_path(&path, paint);
+}
+
+impl<L> View for PhaseResponseView<L>
+where
+    L: Lens<Target = Arc<SignalTaps>>,
+{
+    fn element(&self) -> Option<&'static str> {
+        Some("phase-response-view")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|response_event, _| match response_event {
+            PhaseResponseViewEvent::Update => {
+                let taps = self.taps.get(cx);
+                if self.measurement.update(&taps) {
+                    self.measured = self.measurement.response(&self.chain, taps.sample_rate());
+                    cx.needs_redraw();
+                }
+            }
+            PhaseResponseViewEvent::SetSettings(settings) => {
+                self.chain = AllpassChain::new(*settings);
+                self.compute_analytic();
+                self.measurement.reset();
+                self.measured.clear();
+                cx.needs_redraw();
+            }
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 || self.analytic.is_empty() {
+            return;
+        }
+
+        let background_color = cx.background_color();
+        let font_color = cx.font_color();
+        // The measured response is drawn in the outline color
+        let measured_color = cx.outline_color();
+
+        let mut bg_paint = vg::Paint::default();
+        bg_paint.set_color(background_color);
+        let rect = vg::Rect::from_xywh(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.draw_rect(&rect, &bg_paint);
+
+        let max_freq = MAX_FREQ.min(self.chain.nyquist() as f32);
+        let phase_h = bounds.h * PHASE_PANE_RATIO;
+        let magnitude_top = bounds.y + phase_h;
+        let magnitude_h = bounds.h - phase_h;
+
+        // The phase pane always spans from 0 down to the lowest analytic phase
+        let min_phase = self
+            .analytic
+            .iter()
+            .map(|point| point.phase)
+            .fold(-PI, f32::min);
+
+        let x_of = |frequency: f32| {
+            bounds.x + bounds.w * (frequency / MIN_FREQ).ln() / (max_freq / MIN_FREQ).ln()
+        };
+        let phase_y = |phase: f32| bounds.y + phase_h * (phase / min_phase).clamp(0.0, 1.0);
+        let magnitude_y = |magnitude_db: f32| {
+            let t = (magnitude_db / MAGNITUDE_RANGE_DB).clamp(-1.0, 1.0);
+            magnitude_top + magnitude_h * (0.5 - t * 0.5)
+        };
+
+        // Separate the panes and mark 0 dB
+        let mut grid_paint = vg::Paint::default();
+        grid_paint.set_color(font_color);
+        grid_paint.set_alpha_f(0.25);
+        grid_paint.set_stroke_width(1.0);
+        grid_paint.set_style(vg::PaintStyle::Stroke);
+        let mut grid = vg::Path::new();
+        grid.move_to((bounds.x, magnitude_top));
+        grid.line_to((bounds.right(), magnitude_top));
+        grid.move_to((bounds.x, magnitude_y(0.0)));
+        grid.line_to((bounds.right(), magnitude_y(0.0)));
+        canvas.draw_path(&grid, &grid_paint);
+
+        // The measured curves go underneath so the analytic ones stay readable
+        let mut measured_paint = vg::Paint::default();
+        measured_paint.set_color(measured_color);
+        measured_paint.set_stroke_width(1.5);
+        measured_paint.set_style(vg::PaintStyle::Stroke);
+        measured_paint.set_anti_alias(true);
+        let measured = || {
+            self.measured
+                .iter()
+                .map(move |point| point.filter(|point| point.frequency <= max_freq))
+        };
+        stroke_curve(
+            canvas,
+            measured().map(|point| point.map(|p| (x_of(p.frequency), phase_y(p.phase)))),
+            &measured_paint,
+        );
+        stroke_curve(
+            canvas,
+            measured().map(|point| point.map(|p| (x_of(p.frequency), magnitude_y(p.magnitude_db)))),
+            &measured_paint,
+        );
+
+        let mut analytic_paint = vg::Paint::default();
+        analytic_paint.set_color(font_color);
+        analytic_paint.set_stroke_width(1.5);
+        analytic_paint.set_style(vg::PaintStyle::Stroke);
+        analytic_paint.set_anti_alias(true);
+        stroke_curve(
+            canvas,
+            self.analytic
+                .iter()
+                .map(|p| Some((x_of(p.frequency), phase_y(p.phase)))),
+            &analytic_paint,
+        );
+        stroke_curve(
+            canvas,
+            self.analytic
+                .iter()
+                .map(|p| Some((x_of(p.frequency), magnitude_y(p.magnitude_db)))),
+            &analytic_paint,
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! Hiding and warping the mouse cursor, so knob drags can go on past the edges of the screen.
+//! Every platform gets its own implementation of [`PointerLock`].
+
+pub trait PointerLock {
+    /// The cursor position in screen coordinates.
+    fn position(&self) -> Option<(i32, i32)>;
+    /// Moves the cursor to a position in screen coordinates.
+    fn warp(&mut self, position: (i32, i32));
+    fn hide(&mut self);
+    fn show(&mut self);
+}
+
+/// The pointer lock for the current platform. This is a [`NoPointerLock`] if the cursor can't be
+/// controlled, for instance without an X11 display.
+pub fn platform_pointer_lock() -> Box<dyn PointerLock> {
+    #[cfg(windows)]
+    {
+        Box::new(win32::WindowsPointerLock)
+    }
+    #[cfg(target_os = "linux")]
+    {
+        match x11::X11PointerLock::connect() {
+            Some(lock) => Box::new(lock),
+            None => Box::new(NoPointerLock),
+        }
+    }
+    #[cfg(target_os = "macos")]
+    {
+        Box::new(macos::MacPointerLock)
+    }
+    #[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+    {
+        Box::new(NoPointerLock)
+    }
+}
+
+/// Doesn't know where the cursor is, so knob drags fall back to window coordinates and stop at
+/// the window edge.
+pub struct NoPointerLock;
+
+impl PointerLock for NoPointerLock {
+    fn position(&self) -> Option<(i32, i32)> {
+        None
+    }
+
+    fn warp(&mut self, _position: (i32, i32)) {}
+
+    fn hide(&mut self) {}
+
+    fn show(&mut self) {}
+}
+
+#[cfg(windows)]
+mod win32 {
+    use windows::Win32::{
+        Foundation::POINT,
+        UI::WindowsAndMessaging::{GetCursorPos, SetCursorPos, ShowCursor},
+    };
+
+    use super::PointerLock;
+
+    pub struct WindowsPointerLock;
+
+    impl PointerLock for WindowsPointerLock {
+        fn position(&self) -> Option<(i32, i32)> {
+            let mut pos = POINT::default();
+            unsafe { GetCursorPos(&mut pos) }.ok()?;
+            Some((pos.x, pos.y))
+        }
+
+        fn warp(&mut self, position: (i32, i32)) {
+            let _ = unsafe { SetCursorPos(position.0, position.1) };
+        }
+
+        // `ShowCursor` keeps a display counter, so these loop until the cursor actually changes
+        fn hide(&mut self) {
+            while unsafe { ShowCursor(false) } > 0 {}
+        }
+
+        fn show(&mut self) {
+            while unsafe { ShowCursor(true) } < 0 {}
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod x11 {
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xfixes::ConnectionExt as _;
+    use x11rb::protocol::xproto::{ConnectionExt as _, Window};
+    use x11rb::rust_connection::RustConnection;
+
+    use super::PointerLock;
+
+    /// Uses its own connection to the X server, so it doesn't depend on the host's windowing.
+    /// Closing the connection also undoes the XFixes cursor hiding.
+    pub struct X11PointerLock {
+        conn: RustConnection,
+        root: Window,
+        hidden: bool,
+    }
+
+    impl X11PointerLock {
+        pub fn connect() -> Option<Self> {
+            let (conn, screen_num) = x11rb::connect(None).ok()?;
+            let root = conn.setup().roots.get(screen_num)?.root;
+            // XFixes requests fail until the client has announced the version it supports
+            conn.xfixes_query_version(4, 0).ok()?.reply().ok()?;
+
+            Some(Self {
+                conn,
+                root,
+                hidden: false,
+            })
+        }
+    }
+
+    impl PointerLock for X11PointerLock {
+        fn position(&self) -> Option<(i32, i32)> {
+            let reply = self.conn.query_pointer(self.root).ok()?.reply().ok()?;
+            Some((reply.root_x as i32, reply.root_y as i32))
+        }
+
+        fn warp(&mut self, position: (i32, i32)) {
+            let _ = self.conn.warp_pointer(
+                x11rb::NONE,
+                self.root,
+                0,
+                0,
+                0,
+                0,
+                position.0 as i16,
+                position.1 as i16,
+            );
+            let _ = self.conn.flush();
+        }
+
+        // XFixes counts hide requests per client, so only send one
+        fn hide(&mut self) {
+            if !self.hidden && self.conn.xfixes_hide_cursor(self.root).is_ok() {
+                self.hidden = true;
+                let _ = self.conn.flush();
+            }
+        }
+
+        fn show(&mut self) {
+            if self.hidden && self.conn.xfixes_show_cursor(self.root).is_ok() {
+                self.hidden = false;
+                let _ = self.conn.flush();
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use core_graphics::display::CGDisplay;
+    use core_graphics::event::CGEvent;
+    use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
+    use core_graphics::geometry::CGPoint;
+
+    use super::PointerLock;
+
+    pub struct MacPointerLock;
+
+    impl PointerLock for MacPointerLock {
+        fn position(&self) -> Option<(i32, i32)> {
+            let source = CGEventSource::new(CGEventSourceStateID::CombinedSessionState).ok()?;
+            let location = CGEvent::new(source).ok()?.location();
+            Some((location.x as i32, location.y as i32))
+        }
+
+        fn warp(&mut self, position: (i32, i32)) {
+            let _ = CGDisplay::warp_mouse_cursor_position(CGPoint::new(
+                position.0 as f64,
+                position.1 as f64,
+            ));
+            // Warping suppresses mouse events for a moment unless the mouse is reassociated
+            let _ = CGDisplay::associate_mouse_and_mouse_cursor_position(true);
+        }
+
+        fn hide(&mut self) {
+            let _ = CGDisplay::main().hide_cursor();
+        }
+
+        fn show(&mut self) {
+            let _ = CGDisplay::main().show_cursor();
+        }
+    }
+}
+
+/// Records what a knob drag does with the cursor and plays back scripted cursor positions.
+#[cfg(test)]
+pub mod recording {
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+
+    use super::PointerLock;
+
+    #[derive(Debug, Default)]
+    pub struct RecordingPointerLock {
+        /// Returned by [`position()`][PointerLock::position()] one at a time, `None` once empty.
+        pub positions: RefCell<VecDeque<(i32, i32)>>,
+        pub warps: Vec<(i32, i32)>,
+        pub hide_calls: usize,
+        pub show_calls: usize,
+    }
+
+    impl RecordingPointerLock {
+        pub fn new(positions: &[(i32, i32)]) -> Self {
+            Self {
+                positions: RefCell::new(positions.iter().copied().collect()),
+                ..Self::default()
+            }
+        }
+    }
+
+    impl PointerLock for RecordingPointerLock {
+        fn position(&self) -> Option<(i32, i32)> {
+            self.positions.borrow_mut().pop_front()
+        }
+
+        fn warp(&mut self, position: (i32, i32)) {
+            self.warps.push(position);
+        }
+
+        fn hide(&mut self) {
+            self.hide_calls += 1;
+        }
+
+        fn show(&mut self) {
+            self.show_calls += 1;
+        }
+    }
+}
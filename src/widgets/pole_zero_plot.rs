@@ -0,0 +1,142 @@
+//! A z-plane plot of the poles and zeros of every stage in the allpass cascade.
+
+use vizia_plug::vizia::{prelude::*, vg};
+
+use crate::analysis::allpass::{AllpassChain, ChainSettings, NEAR_UNSTABLE_RADIUS};
+
+/// The size of the pole crosses and zero circles, in logical pixels.
+const MARKER_SIZE: f32 = 4.0;
+
+pub enum PoleZeroPlotEvent {
+    SetSettings(ChainSettings),
+}
+
+#[derive(Lens)]
+pub struct PoleZeroPlot {
+    chain: AllpassChain,
+    /// A short summary shown in the corner of the plot.
+    info: String,
+    near_unstable: bool,
+}
+
+impl PoleZeroPlot {
+    pub fn new<L>(cx: &mut Context, settings: L) -> Handle<'_, Self>
+    where
+        L: Lens<Target = ChainSettings>,
+    {
+        Self {
+            chain: AllpassChain::default(),
+            info: String::new(),
+            near_unstable: false,
+        }
+        .build(cx, |cx| {
+            Binding::new(cx, settings, |cx, settings| {
+                let settings = settings.get(cx);
+                cx.emit(PoleZeroPlotEvent::SetSettings(settings));
+            });
+
+            Label::new(cx, PoleZeroPlot::info)
+                .class("analysis-view__info")
+                .toggle_class("unstable", PoleZeroPlot::near_unstable);
+        })
+    }
+}
+
+impl View for PoleZeroPlot {
+    fn element(&self) -> Option<&'static str> {
+        Some("pole-zero-plot")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|plot_event, _| match plot_event {
+            PoleZeroPlotEvent::SetSettings(settings) => {
+                self.chain = AllpassChain::new(*settings);
+                self.near_unstable = self.chain.is_near_unstable();
+                // The stage closest to the unit circle rings the longest
+                let max_radius = self
+                    .chain
+                    .stages
+                    .iter()
+                    .map(|stage| stage.pole_radius())
+                    .reduce(f64::max);
+                self.info = match max_radius {
+                    Some(radius) => format!(
+                        "{} STAGES  r={:.5}{}",
+                        self.chain.stages.len(),
+                        radius,
+                        if self.near_unstable { "  RINGING" } else { "" }
+                    ),
+                    None => String::from("BYPASSED"),
+                };
+                cx.needs_redraw();
+            }
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let background_color = cx.background_color();
+        let font_color = cx.font_color();
+        // Stages close to instability are drawn in the outline color
+        let warning_color = cx.outline_color();
+
+        let mut bg_paint = vg::Paint::default();
+        bg_paint.set_color(background_color);
+        let rect = vg::Rect::from_xywh(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.draw_rect(&rect, &bg_paint);
+
+        let (center_x, center_y) = bounds.center();
+        // Leave some room for zeros just outside of the unit circle
+        let radius = bounds.w.min(bounds.h) / 2.0 * 0.8;
+        let to_screen =
+            |re: f64, im: f64| (center_x + re as f32 * radius, center_y - im as f32 * radius);
+
+        // The unit circle and the axes
+        let mut grid_paint = vg::Paint::default();
+        grid_paint.set_color(font_color);
+        grid_paint.set_alpha_f(0.25);
+        grid_paint.set_stroke_width(1.0);
+        grid_paint.set_style(vg::PaintStyle::Stroke);
+        grid_paint.set_anti_alias(true);
+        canvas.draw_circle((center_x, center_y), radius, &grid_paint);
+        let mut axes = vg::Path::new();
+        axes.move_to((bounds.left(), center_y));
+        axes.line_to((bounds.right(), center_y));
+        axes.move_to((center_x, bounds.top()));
+        axes.line_to((center_x, bounds.bottom()));
+        canvas.draw_path(&axes, &grid_paint);
+
+        let mut marker_paint = vg::Paint::default();
+        marker_paint.set_stroke_width(1.5);
+        marker_paint.set_style(vg::PaintStyle::Stroke);
+        marker_paint.set_anti_alias(true);
+
+        for stage in &self.chain.stages {
+            if stage.pole_radius() >= NEAR_UNSTABLE_RADIUS {
+                marker_paint.set_color(warning_color);
+            } else {
+                marker_paint.set_color(font_color);
+            }
+
+            let pole = stage.pole();
+            let zero = stage.zero();
+            // Poles and zeros come in conjugate pairs
+            for sign in [1.0, -1.0] {
+                let (x, y) = to_screen(pole.re, pole.im * sign);
+                let mut cross = vg::Path::new();
+                cross.move_to((x - MARKER_SIZE, y - MARKER_SIZE));
+                cross.line_to((x + MARKER_SIZE, y + MARKER_SIZE));
+                cross.move_to((x - MARKER_SIZE, y + MARKER_SIZE));
+                cross.line_to((x + MARKER_SIZE, y - MARKER_SIZE));
+                canvas.draw_path(&cross, &marker_paint);
+
+                let (x, y) = to_screen(zero.re, zero.im * sign);
+                canvas.draw_circle((x, y), MARKER_SIZE, &marker_paint);
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! The grip in the bottom right corner of the editor that resizes its window. Unlike vizia-plug's
+//! `ResizeHandle` this changes the window's logical size and lets the layout reflow, the UI scale
+//! is left to the presets in the info panel.
+
+use vizia_plug::vizia::{prelude::*, vg};
+
+pub struct ResizeCorner {
+    /// The cursor position and the window's size in logical pixels when the drag started.
+    drag_start: Option<((f32, f32), (f32, f32))>,
+    on_resize: Box<dyn Fn(&mut EventContext, (u32, u32))>,
+}
+
+impl ResizeCorner {
+    /// `on_resize` is called with the new logical window size while the corner is dragged.
+    pub fn new(
+        cx: &mut Context,
+        on_resize: impl Fn(&mut EventContext, (u32, u32)) + 'static,
+    ) -> Handle<'_, Self> {
+        Self {
+            drag_start: None,
+            on_resize: Box::new(on_resize),
+        }
+        .build(cx, |_| {})
+    }
+
+    fn logical_cursor(cx: &EventContext) -> (f32, f32) {
+        let scale = cx.scale_factor();
+        (cx.mouse().cursor_x / scale, cx.mouse().cursor_y / scale)
+    }
+}
+
+impl View for ResizeCorner {
+    fn element(&self) -> Option<&'static str> {
+        Some("resize-corner")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, meta| match window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                let scale = cx.scale_factor();
+                let window = cx.with_current(Entity::root(), |cx| cx.bounds());
+                self.drag_start = Some((
+                    Self::logical_cursor(cx),
+                    (window.w / scale, window.h / scale),
+                ));
+                cx.capture();
+                cx.set_active(true);
+                meta.consume();
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if self.drag_start.take().is_some() {
+                    cx.release();
+                    cx.set_active(false);
+                    meta.consume();
+                }
+            }
+            WindowEvent::MouseMove(_, _) => {
+                if let Some(((start_x, start_y), (width, height))) = self.drag_start {
+                    let (x, y) = Self::logical_cursor(cx);
+                    let size = (
+                        (width + x - start_x).round().max(0.0) as u32,
+                        (height + y - start_y).round().max(0.0) as u32,
+                    );
+                    (self.on_resize)(cx, size);
+                    meta.consume();
+                }
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        // Three diagonal lines towards the corner, in the font color
+        let mut paint = vg::Paint::default();
+        paint.set_color(cx.font_color());
+        paint.set_stroke_width(1.0);
+        paint.set_stroke_cap(vg::PaintCap::Round);
+        paint.set_style(vg::PaintStyle::Stroke);
+        paint.set_anti_alias(true);
+
+        for fraction in [0.25, 0.5, 0.75] {
+            canvas.draw_line(
+                (bounds.right() - bounds.w * fraction, bounds.bottom()),
+                (bounds.right(), bounds.bottom() - bounds.h * fraction),
+                &paint,
+            );
+        }
+    }
+}
@@ -0,0 +1,391 @@
+//! A scrolling spectrogram with a logarithmic frequency axis.
+
+use realfft::num_complex::Complex;
+use realfft::{RealFftPlanner, RealToComplex};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::Arc;
+use vizia_plug::vizia::{prelude::*, vg};
+
+use crate::analysis::taps::{SampleRing, SignalTaps};
+
+const FFT_SIZE: usize = 2048;
+const HOP_SIZE: usize = 512;
+/// How many spectra are kept around. Every column is one hop, so this is the scroll length.
+const MAX_COLUMNS: usize = 256;
+
+const MIN_FREQ: f32 = 20.0;
+const MAX_FREQ: f32 = 20000.0;
+const MIN_DB: f32 = -100.0;
+const MAX_DB: f32 = 0.0;
+
+/// The height of a single drawn frequency band, in logical pixels.
+const ROW_HEIGHT: f32 = 2.0;
+
+pub enum SpectrogramViewEvent {
+    Update,
+    SetSplit(bool),
+}
+
+/// Turns the samples of one tap into a history of magnitude spectra.
+struct Analyzer {
+    read_pos: usize,
+    pending: Vec<f32>,
+    window: Vec<f32>,
+    fft: Arc<dyn RealToComplex<f32>>,
+    fft_input: Vec<f32>,
+    fft_output: Vec<Complex<f32>>,
+    /// Magnitudes in dB per FFT bin, oldest column first.
+    columns: VecDeque<Vec<f32>>,
+    /// The number of columns added so far, including the ones dropped from `columns` since.
+    total_columns: u64,
+    /// Filled in while drawing, where the size and colors are known.
+    image: RefCell<ColumnImage>,
+}
+
+impl Analyzer {
+    fn new(planner: &mut RealFftPlanner<f32>) -> Self {
+        let fft = planner.plan_fft_forward(FFT_SIZE);
+        let window = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / FFT_SIZE as f32).cos())
+            .collect();
+
+        Self {
+            read_pos: 0,
+            pending: Vec::with_capacity(FFT_SIZE * 2),
+            window,
+            fft_input: fft.make_input_vec(),
+            fft_output: fft.make_output_vec(),
+            fft,
+            columns: VecDeque::with_capacity(MAX_COLUMNS),
+            total_columns: 0,
+            image: RefCell::default(),
+        }
+    }
+
+    /// Drops the history, for when it's hidden.
+    fn clear(&mut self) {
+        self.columns.clear();
+        *self.image.get_mut() = ColumnImage::default();
+    }
+
+    /// Skips everything in the ring without analysing it.
+    fn skip(&mut self, ring: &SampleRing) {
+        self.read_pos = ring.written();
+        self.pending.clear();
+    }
+
+    /// Pulls new samples out of the ring and appends a column for every full hop.
+    fn update<const CHANNELS: usize>(&mut self, ring: &SampleRing<CHANNELS>) -> bool {
+        self.read_pos = ring.read_since(self.read_pos, &mut self.pending);
+
+        // Don't bother analysing more than what fits on screen
+        let max_pending = FFT_SIZE + HOP_SIZE * MAX_COLUMNS;
+        if self.pending.len() > max_pending {
+            self.pending.drain(..self.pending.len() - max_pending);
+        }
+
+        let mut added = false;
+        while self.pending.len() >= FFT_SIZE {
+            for ((input, sample), window) in self
+                .fft_input
+                .iter_mut()
+                .zip(&self.pending[..FFT_SIZE])
+                .zip(&self.window)
+            {
+                *input = sample * window;
+            }
+
+            if self
+                .fft
+                .process(&mut self.fft_input, &mut self.fft_output)
+                .is_ok()
+            {
+                // The Hann window halves the amplitude, and we only look at one side
+                let scale = 4.0 / FFT_SIZE as f32;
+                let column = if self.columns.len() >= MAX_COLUMNS {
+                    self.columns.pop_front()
+                } else {
+                    None
+                };
+                let mut column = column.unwrap_or_default();
+                column.clear();
+                column.extend(
+                    self.fft_output
+                        .iter()
+                        .map(|bin| 20.0 * (bin.norm() * scale).max(1e-10).log10()),
+                );
+                self.columns.push_back(column);
+                self.total_columns += 1;
+                added = true;
+            }
+
+            self.pending.drain(..HOP_SIZE);
+        }
+
+        added
+    }
+}
+
+pub struct SpectrogramView<L>
+where
+    L: Lens<Target = Arc<SignalTaps>>,
+{
+    taps: L,
+    pre: Analyzer,
+    post: Analyzer,
+    split: bool,
+}
+
+impl<L> SpectrogramView<L>
+where
+    L: Lens<Target = Arc<SignalTaps>>,
+{
+    /// Creates a spectrogram of the post signal. When `split` is set, the pre signal is shown on
+    /// the left half so the smearing caused by the disperser can be compared directly.
+    pub fn new<S>(cx: &mut Context, taps: L, split: S) -> Handle<'_, Self>
+    where
+        S: Lens<Target = bool>,
+    {
+        let mut planner = RealFftPlanner::new();
+
+        Self {
+            taps,
+            pre: Analyzer::new(&mut planner),
+            post: Analyzer::new(&mut planner),
+            split: false,
+        }
+        .build(cx, |cx| {
+            Binding::new(cx, taps.map(|taps| taps.post.written()), |cx, _| {
+                cx.emit(SpectrogramViewEvent::Update);
+            });
+            Binding::new(cx, split, |cx, split| {
+                let split = split.get(cx);
+                cx.emit(SpectrogramViewEvent::SetSplit(split));
+            });
+        })
+    }
+
+    /// Draws the analyzer's history into `bounds` as a single image.
+    fn draw_columns(
+        canvas: &Canvas,
+        analyzer: &Analyzer,
+        bounds: BoundingBox,
+        sample_rate: f32,
+        color_stops: &[(f32, Color); 4],
+    ) {
+        if analyzer.columns.is_empty() {
+            return;
+        }
+
+        let num_rows = (bounds.h / ROW_HEIGHT).ceil().max(1.0) as usize;
+        let mut image = analyzer.image.borrow_mut();
+        image.update(analyzer, num_rows, sample_rate, color_stops);
+
+        let info = vg::ImageInfo::new(
+            (MAX_COLUMNS as i32, num_rows as i32),
+            vg::ColorType::RGBA8888,
+            vg::AlphaType::Unpremul,
+            None,
+        );
+        let Some(pixels) =
+            vg::images::raster_from_data(&info, vg::Data::new_copy(&image.pixels), MAX_COLUMNS * 4)
+        else {
+            return;
+        };
+
+        let rect = vg::Rect::from_xywh(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.draw_image_rect(&pixels, None, rect, &vg::Paint::default());
+    }
+}
+
+/// An analyzer's columns as pixels, one per column and frequency band with the newest column on
+/// the right. New columns shift the existing pixels to the left, so only those have to be mapped
+/// to colors and the whole history can be drawn as a single image.
+#[derive(Default)]
+struct ColumnImage {
+    /// RGBA, `MAX_COLUMNS` pixels per row. Bands without a column yet are transparent.
+    pixels: Vec<u8>,
+    /// The FFT bins covered by every row, starting at the top with the highest frequencies.
+    bands: Vec<Range<usize>>,
+    /// The row count, sample rate and colors the pixels were rendered with. Changing any of
+    /// these means rendering the whole history again.
+    layout: Option<(usize, f32, [(f32, Color); 4])>,
+    /// [`Analyzer::total_columns`] as of the last update.
+    rendered_columns: u64,
+}
+
+impl ColumnImage {
+    fn update(
+        &mut self,
+        analyzer: &Analyzer,
+        num_rows: usize,
+        sample_rate: f32,
+        color_stops: &[(f32, Color); 4],
+    ) {
+        let columns = &analyzer.columns;
+        let layout = Some((num_rows, sample_rate, *color_stops));
+        if self.layout != layout {
+            self.layout = layout;
+            self.bands = frequency_bands(num_rows, sample_rate);
+            self.pixels.clear();
+            self.pixels.resize(num_rows * MAX_COLUMNS * 4, 0);
+            self.rendered_columns = analyzer.total_columns - columns.len() as u64;
+        }
+
+        let new_columns =
+            ((analyzer.total_columns - self.rendered_columns) as usize).min(columns.len());
+        self.rendered_columns = analyzer.total_columns;
+        if new_columns == 0 {
+            return;
+        }
+
+        let row_bytes = MAX_COLUMNS * 4;
+        for row in self.pixels.chunks_exact_mut(row_bytes) {
+            row.copy_within(new_columns * 4.., 0);
+        }
+
+        let first_x = MAX_COLUMNS - new_columns;
+        for (i, column) in columns.range(columns.len() - new_columns..).enumerate() {
+            for (row, band) in self.bands.iter().enumerate() {
+                // Use the loudest bin in the band so narrow peaks don't vanish up top
+                let db = column[band.clone()].iter().copied().fold(MIN_DB, f32::max);
+                let level = ((db - MIN_DB) / (MAX_DB - MIN_DB)).clamp(0.0, 1.0);
+                let rgba = if level <= 0.0 {
+                    [0; 4]
+                } else {
+                    let color = color_map(color_stops, level);
+                    [color.r(), color.g(), color.b(), color.a()]
+                };
+
+                let offset = row * row_bytes + (first_x + i) * 4;
+                self.pixels[offset..offset + 4].copy_from_slice(&rgba);
+            }
+        }
+    }
+}
+
+/// The FFT bins shown in each of `num_rows` rows on a logarithmic frequency axis, top row first.
+fn frequency_bands(num_rows: usize, sample_rate: f32) -> Vec<Range<usize>> {
+    let bin_width = sample_rate / FFT_SIZE as f32;
+    let max_freq = MAX_FREQ.min(sample_rate / 2.0);
+    let num_bins = FFT_SIZE / 2 + 1;
+
+    (0..num_rows)
+        .map(|row| {
+            let t_hi = 1.0 - row as f32 / num_rows as f32;
+            let t_lo = 1.0 - (row + 1) as f32 / num_rows as f32;
+            let freq_hi = MIN_FREQ * (max_freq / MIN_FREQ).powf(t_hi);
+            let freq_lo = MIN_FREQ * (max_freq / MIN_FREQ).powf(t_lo);
+            let bin_lo = ((freq_lo / bin_width).floor() as usize).min(num_bins);
+            let bin_hi = ((freq_hi / bin_width).ceil() as usize)
+                .max(bin_lo + 1)
+                .min(num_bins);
+            bin_lo..bin_hi
+        })
+        .collect()
+}
+
+/// Maps a level between 0 and 1 onto the gradient between the color stops.
+fn color_map(stops: &[(f32, Color); 4], level: f32) -> Color {
+    let upper = stops
+        .iter()
+        .position(|(stop, _)| *stop >= level)
+        .unwrap_or(stops.len() - 1)
+        .max(1);
+    let (lo_t, lo) = stops[upper - 1];
+    let (hi_t, hi) = stops[upper];
+    let t = ((level - lo_t) / (hi_t - lo_t)).clamp(0.0, 1.0);
+    let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+
+    Color::rgba(
+        lerp(lo.r(), hi.r()),
+        lerp(lo.g(), hi.g()),
+        lerp(lo.b(), hi.b()),
+        lerp(lo.a(), hi.a()),
+    )
+}
+
+impl<L> View for SpectrogramView<L>
+where
+    L: Lens<Target = Arc<SignalTaps>>,
+{
+    fn element(&self) -> Option<&'static str> {
+        Some("spectrogram-view")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|spectrogram_event, _| match spectrogram_event {
+            SpectrogramViewEvent::Update => {
+                let taps = self.taps.get(cx);
+                let mut changed = self.post.update(&taps.post);
+                if self.split {
+                    changed |= self.pre.update(&taps.pre);
+                } else {
+                    self.pre.skip(&taps.pre);
+                }
+
+                if changed {
+                    cx.needs_redraw();
+                }
+            }
+            SpectrogramViewEvent::SetSplit(split) => {
+                self.split = *split;
+                if !self.split {
+                    self.pre.clear();
+                }
+                cx.needs_redraw();
+            }
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let background_color = cx.background_color();
+        let mut bg_paint = vg::Paint::default();
+        bg_paint.set_color(background_color);
+        let rect = vg::Rect::from_xywh(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.draw_rect(&rect, &bg_paint);
+
+        let sample_rate = self.taps.get(cx).sample_rate();
+        // Silence fades into the background, through the border and font colors up to the
+        // outline color for the loudest bins
+        let color_stops = [
+            (0.0, background_color),
+            (0.45, cx.border_color()),
+            (0.8, cx.font_color()),
+            (1.0, cx.outline_color()),
+        ];
+
+        if self.split {
+            let half_w = bounds.w / 2.0;
+            let left =
+                BoundingBox::from_min_max(bounds.x, bounds.y, bounds.x + half_w, bounds.bottom());
+            let right = BoundingBox::from_min_max(
+                bounds.x + half_w,
+                bounds.y,
+                bounds.right(),
+                bounds.bottom(),
+            );
+            Self::draw_columns(canvas, &self.pre, left, sample_rate, &color_stops);
+            Self::draw_columns(canvas, &self.post, right, sample_rate, &color_stops);
+
+            // Divider between pre and post
+            let mut divider = vg::Path::new();
+            divider.move_to((bounds.x + half_w, bounds.y));
+            divider.line_to((bounds.x + half_w, bounds.bottom()));
+            let mut divider_paint = vg::Paint::default();
+            divider_paint.set_color(cx.font_color());
+            divider_paint.set_stroke_width(1.0);
+            divider_paint.set_style(vg::PaintStyle::Stroke);
+            canvas.draw_path(&divider, &divider_paint);
+        } else {
+            Self::draw_columns(canvas, &self.post, bounds, sample_rate, &color_stops);
+        }
+    }
+}
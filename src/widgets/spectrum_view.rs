@@ -1,72 +1,213 @@
+//! A live magnitude spectrum of the post signal, with the pre signal drawn as an outline.
+
+use realfft::num_complex::Complex;
+use realfft::{RealFftPlanner, RealToComplex};
+use std::sync::Arc;
 use vizia_plug::vizia::{prelude::*, vg};
 
+use crate::analysis::taps::{SampleRing, SignalTaps};
+
+const FFT_SIZE: usize = 4096;
+const HOP_SIZE: usize = 1024;
+/// How far the displayed level may fall per analysed hop, in decibels.
+const FALL_DB_PER_HOP: f32 = 3.0;
+
+const MIN_FREQ: f32 = 20.0;
+const MAX_FREQ: f32 = 20000.0;
+const MIN_DB: f32 = -100.0;
+const MAX_DB: f32 = 0.0;
+
+pub enum SpectrumViewEvent {
+    Update,
+}
+
+/// Turns the samples of one tap into a smoothed magnitude spectrum.
+struct Analyzer {
+    read_pos: usize,
+    pending: Vec<f32>,
+    window: Vec<f32>,
+    fft: Arc<dyn RealToComplex<f32>>,
+    fft_input: Vec<f32>,
+    fft_output: Vec<Complex<f32>>,
+    /// Magnitudes in dB per FFT bin.
+    magnitudes: Vec<f32>,
+}
+
+impl Analyzer {
+    fn new(planner: &mut RealFftPlanner<f32>) -> Self {
+        let fft = planner.plan_fft_forward(FFT_SIZE);
+        let window = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / FFT_SIZE as f32).cos())
+            .collect();
+
+        Self {
+            read_pos: 0,
+            pending: Vec::with_capacity(FFT_SIZE * 2),
+            window,
+            fft_input: fft.make_input_vec(),
+            fft_output: fft.make_output_vec(),
+            fft,
+            magnitudes: vec![MIN_DB; FFT_SIZE / 2 + 1],
+        }
+    }
+
+    /// Pulls new samples out of the ring and updates the spectrum for every full hop.
+    fn update<const CHANNELS: usize>(&mut self, ring: &SampleRing<CHANNELS>) -> bool {
+        self.read_pos = ring.read_since(self.read_pos, &mut self.pending);
+
+        // Only the latest frame is visible, so older hops can be skipped entirely
+        let max_pending = FFT_SIZE + HOP_SIZE;
+        if self.pending.len() > max_pending {
+            self.pending.drain(..self.pending.len() - max_pending);
+        }
+
+        let mut changed = false;
+        while self.pending.len() >= FFT_SIZE {
+            for ((input, sample), window) in self
+                .fft_input
+                .iter_mut()
+                .zip(&self.pending[..FFT_SIZE])
+                .zip(&self.window)
+            {
+                *input = sample * window;
+            }
+
+            if self
+                .fft
+                .process(&mut self.fft_input, &mut self.fft_output)
+                .is_ok()
+            {
+                // The Hann window halves the amplitude, and we only look at one side
+                let scale = 4.0 / FFT_SIZE as f32;
+                for (magnitude, bin) in self.magnitudes.iter_mut().zip(&self.fft_output) {
+                    let db = 20.0 * (bin.norm() * scale).max(1e-10).log10();
+                    *magnitude = db.max(*magnitude - FALL_DB_PER_HOP);
+                }
+                changed = true;
+            }
+
+            self.pending.drain(..HOP_SIZE);
+        }
+
+        changed
+    }
+}
+
 pub struct SpectrumView<L>
 where
-    L: Lens<Target = Vec<f32>>,
+    L: Lens<Target = Arc<SignalTaps>>,
 {
-    data: L,
+    taps: L,
+    pre: Analyzer,
+    post: Analyzer,
 }
 
 impl<L> SpectrumView<L>
 where
-    L: Lens<Target = Vec<f32>>,
+    L: Lens<Target = Arc<SignalTaps>>,
 {
-    pub fn new(cx: &mut Context, lens: L) -> Handle<'_, Self> {
-        Self { data: lens.clone() }.build(cx, |cx| {
-            Binding::new(cx, lens, |cx, _| {
-                cx.needs_redraw(cx.current());
+    pub fn new(cx: &mut Context, taps: L) -> Handle<'_, Self> {
+        let mut planner = RealFftPlanner::new();
+
+        Self {
+            taps,
+            pre: Analyzer::new(&mut planner),
+            post: Analyzer::new(&mut planner),
+        }
+        .build(cx, |cx| {
+            Binding::new(cx, taps.map(|taps| taps.post.written()), |cx, _| {
+                cx.emit(SpectrumViewEvent::Update);
             });
         })
     }
+
+    /// Builds the outline of a spectrum on a logarithmic frequency axis.
+    fn spectrum_path(magnitudes: &[f32], bounds: BoundingBox, sample_rate: f32) -> vg::Path {
+        let bin_width = sample_rate / FFT_SIZE as f32;
+        let max_freq = MAX_FREQ.min(sample_rate / 2.0);
+        let y_of = |db: f32| {
+            bounds.bottom() - bounds.h * ((db - MIN_DB) / (MAX_DB - MIN_DB)).clamp(0.0, 1.0)
+        };
+
+        // One point per pixel column, using the loudest bin that falls in it
+        let columns = bounds.w.floor().max(1.0) as usize;
+        let mut path = vg::Path::new();
+        path.move_to((bounds.x, bounds.bottom()));
+        for column in 0..columns {
+            let t_lo = column as f32 / columns as f32;
+            let t_hi = (column + 1) as f32 / columns as f32;
+            let freq_lo = MIN_FREQ * (max_freq / MIN_FREQ).powf(t_lo);
+            let freq_hi = MIN_FREQ * (max_freq / MIN_FREQ).powf(t_hi);
+            let bin_lo = ((freq_lo / bin_width).floor() as usize).min(magnitudes.len() - 1);
+            let bin_hi = ((freq_hi / bin_width).ceil() as usize)
+                .max(bin_lo + 1)
+                .min(magnitudes.len());
+
+            let db = magnitudes[bin_lo..bin_hi]
+                .iter()
+                .copied()
+                .fold(MIN_DB, f32::max);
+            path.line_to((bounds.x + column as f32 + 0.5, y_of(db)));
+        }
+        path.line_to((bounds.right(), bounds.bottom()));
+
+        path
+    }
 }
 
 impl<L> View for SpectrumView<L>
 where
-    L: Lens<Target = Vec<f32>>,
+    L: Lens<Target = Arc<SignalTaps>>,
 {
     fn element(&self) -> Option<&'static str> {
         Some("spectrum-view")
     }
 
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|spectrum_event, _| match spectrum_event {
+            SpectrumViewEvent::Update => {
+                let taps = self.taps.get(cx);
+                let mut changed = self.post.update(&taps.post);
+                changed |= self.pre.update(&taps.pre);
+
+                if changed {
+                    cx.needs_redraw();
+                }
+            }
+        });
+    }
+
     fn draw(&self, cx: &mut DrawContext, canvas: &Canvas) {
         let bounds = cx.bounds();
         if bounds.w == 0.0 || bounds.h == 0.0 {
             return;
         }
 
-        let data_vec = self.data.get(cx);
-        if data_vec.is_empty() {
-            return;
-        }
-
         let background_color = cx.background_color();
+        let stroke_color = cx.font_color();
+
         let mut bg_paint = vg::Paint::default();
         bg_paint.set_color(background_color);
         let rect = vg::Rect::from_xywh(bounds.x, bounds.y, bounds.w, bounds.h);
         canvas.draw_rect(&rect, &bg_paint);
 
-        let stroke_color = cx.font_color();
-
-        let max_val = data_vec.iter().copied().fold(0.0f32, f32::max);
-
-        let num_bins = data_vec.len();
-        let bin_width = bounds.w / num_bins as f32;
-
-        let mut spectrum_path = vg::Path::new();
-
-        for (i, &val) in data_vec.iter().enumerate() {
-            let normalized_val = if max_val > 0.0 { val / max_val } else { 0.0 };
-            let bar_height = (normalized_val * bounds.h).clamp(0.0, bounds.h);
-            let x = bounds.x + i as f32 * bin_width;
-            let y = bounds.y + bounds.h - bar_height;
-
-            spectrum_path.add_rect(vg::Rect::new(x, y, bin_width, bar_height), None);
-        }
+        let sample_rate = self.taps.get(cx).sample_rate();
 
+        let mut post_path = Self::spectrum_path(&self.post.magnitudes, bounds, sample_rate);
+        post_path.close();
         let mut fill_paint = vg::Paint::default();
         fill_paint.set_color(stroke_color);
+        fill_paint.set_alpha_f(0.6);
         fill_paint.set_style(vg::PaintStyle::Fill);
         fill_paint.set_anti_alias(true);
-        canvas.draw_path(&spectrum_path, &fill_paint);
+        canvas.draw_path(&post_path, &fill_paint);
+
+        let pre_path = Self::spectrum_path(&self.pre.magnitudes, bounds, sample_rate);
+        let mut stroke_paint = vg::Paint::default();
+        stroke_paint.set_color(stroke_color);
+        stroke_paint.set_stroke_width(1.0);
+        stroke_paint.set_style(vg::PaintStyle::Stroke);
+        stroke_paint.set_anti_alias(true);
+        canvas.draw_path(&pre_path, &stroke_paint);
     }
 }
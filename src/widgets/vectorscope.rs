@@ -0,0 +1,161 @@
+//! A Lissajous style vectorscope. Mono signals show up as a vertical line, out of phase signals
+//! as a horizontal one.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use vizia_plug::vizia::{prelude::*, vg};
+
+use crate::analysis::taps::SignalTaps;
+
+/// The most points we'll ever draw, no matter how long the integration time is.
+const MAX_POINTS: usize = 4096;
+
+pub enum VectorscopeEvent {
+    Update,
+    SetIntegrationTime(f32),
+}
+
+pub struct Vectorscope<L>
+where
+    L: Lens<Target = Arc<SignalTaps>>,
+{
+    taps: L,
+    integration_ms: f32,
+    read_pos: usize,
+    left: Vec<f32>,
+    right: Vec<f32>,
+    /// Side/mid pairs, oldest first.
+    points: VecDeque<(f32, f32)>,
+}
+
+impl<L> Vectorscope<L>
+where
+    L: Lens<Target = Arc<SignalTaps>>,
+{
+    /// Creates a vectorscope for the output signal showing the last `integration_ms` milliseconds.
+    pub fn new<I>(cx: &mut Context, taps: L, integration_ms: I) -> Handle<'_, Self>
+    where
+        I: Lens<Target = f32>,
+    {
+        Self {
+            taps,
+            integration_ms: 300.0,
+            read_pos: 0,
+            left: Vec::new(),
+            right: Vec::new(),
+            points: VecDeque::with_capacity(MAX_POINTS),
+        }
+        .build(cx, |cx| {
+            Binding::new(cx, taps.map(|taps| taps.post.written()), |cx, _| {
+                cx.emit(VectorscopeEvent::Update);
+            });
+            Binding::new(cx, integration_ms, |cx, integration_ms| {
+                let integration_ms = integration_ms.get(cx);
+                cx.emit(VectorscopeEvent::SetIntegrationTime(integration_ms));
+            });
+        })
+    }
+}
+
+impl<L> View for Vectorscope<L>
+where
+    L: Lens<Target = Arc<SignalTaps>>,
+{
+    fn element(&self) -> Option<&'static str> {
+        Some("vectorscope")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|scope_event, _| match scope_event {
+            VectorscopeEvent::Update => {
+                let taps = self.taps.get(cx);
+                self.left.clear();
+                self.right.clear();
+                self.read_pos =
+                    taps.post
+                        .read_stereo_since(self.read_pos, &mut self.left, &mut self.right);
+                if self.left.is_empty() {
+                    return;
+                }
+
+                // Long integration times are decimated so we never draw more than `MAX_POINTS`
+                let window_samples =
+                    (self.integration_ms / 1000.0 * taps.sample_rate()).max(1.0) as usize;
+                let stride = window_samples.div_ceil(MAX_POINTS).max(1);
+                let max_points = (window_samples / stride).clamp(1, MAX_POINTS);
+
+                for (l, r) in self.left.iter().zip(&self.right).step_by(stride) {
+                    let side = (r - l) * std::f32::consts::FRAC_1_SQRT_2;
+                    let mid = (l + r) * std::f32::consts::FRAC_1_SQRT_2;
+                    self.points.push_back((side, mid));
+                }
+                while self.points.len() > max_points {
+                    self.points.pop_front();
+                }
+
+                cx.needs_redraw();
+            }
+            VectorscopeEvent::SetIntegrationTime(integration_ms) => {
+                self.integration_ms = *integration_ms;
+            }
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let background_color = cx.background_color();
+        let border_color = cx.border_color();
+        let border_width = cx.border_width();
+        let font_color = cx.font_color();
+
+        let mut paint = vg::Paint::default();
+        paint.set_color(background_color);
+        let rect = vg::Rect::from_xywh(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.draw_rect(&rect, &paint);
+
+        let (center_x, center_y) = bounds.center();
+        let radius = bounds.w.min(bounds.h) / 2.0;
+
+        // Mono and side axes
+        let mut axis_paint = vg::Paint::default();
+        axis_paint.set_color(font_color);
+        axis_paint.set_alpha_f(0.2);
+        axis_paint.set_stroke_width(1.0);
+        axis_paint.set_style(vg::PaintStyle::Stroke);
+        let mut axes = vg::Path::new();
+        axes.move_to((center_x, center_y - radius));
+        axes.line_to((center_x, center_y + radius));
+        axes.move_to((center_x - radius, center_y));
+        axes.line_to((center_x + radius, center_y));
+        canvas.draw_path(&axes, &axis_paint);
+
+        let mut trace = vg::Path::new();
+        for (i, (side, mid)) in self.points.iter().enumerate() {
+            let x = center_x + side.clamp(-1.0, 1.0) * radius;
+            let y = center_y - mid.clamp(-1.0, 1.0) * radius;
+            if i == 0 {
+                trace.move_to((x, y));
+            } else {
+                trace.line_to((x, y));
+            }
+        }
+
+        let mut trace_paint = vg::Paint::default();
+        trace_paint.set_color(font_color);
+        trace_paint.set_alpha_f(0.6);
+        trace_paint.set_stroke_width(1.0);
+        trace_paint.set_style(vg::PaintStyle::Stroke);
+        trace_paint.set_anti_alias(true);
+        canvas.draw_path(&trace, &trace_paint);
+
+        let mut border_paint = vg::Paint::default();
+        border_paint.set_color(border_color);
+        border_paint.set_stroke_width(border_width);
+        border_paint.set_style(vg::PaintStyle::Stroke);
+        canvas.draw_rect(&rect, &border_paint);
+    }
+}
@@ -1,103 +1,240 @@
 use std::collections::VecDeque;
+use std::sync::Arc;
 use vizia_plug::vizia::{prelude::*, vg};
 
+use crate::analysis::taps::SignalTaps;
+
+/// The portion of the oscilloscope's timebase shown before the trigger point.
+const SCOPE_PRE_TRIGGER: f32 = 0.1;
+/// The pre signal needs to drop below minus this value before a new rising edge counts.
+const SCOPE_TRIGGER_HYSTERESIS: f32 = 0.01;
+/// The number of vertical grid lines drawn in oscilloscope mode.
+const SCOPE_DIVISIONS: usize = 10;
+
 pub enum WaveformViewEvent {
-    AddSample1(f32),
-    AddSample2(f32),
+    ScopeUpdate(Arc<SignalTaps>),
+    SetScopeEnabled(bool),
+    SetScopeTimebase(f32),
+    SetScopeFrozen(bool),
+}
+
+pub enum WaveformTraceEvent {
+    AddSample(f32),
+    /// Replaces the envelope with a raw oscilloscope trace, or goes back to the envelope on `None`.
+    SetScopeTrace(Option<Vec<f32>>),
 }
 
+/// A view that draws any number of signal envelopes on top of each other. Traces are added with
+/// [`WaveformViewHandle::trace()`], and each one is styled through its own CSS class:
+/// `background-color` fills the envelope, `color` and `border-width` set the outline, and
+/// `opacity` applies to the whole trace. The view's own `border-color` is used for the centre line
+/// and the oscilloscope grid.
 pub struct WaveformView {
-    buffer1: VecDeque<f32>,
-    buffer2: VecDeque<f32>,
     max_samples: usize,
+    traces: Vec<Entity>,
+    scope: Scope,
+}
+
+/// A single trace of a [`WaveformView`]. These cover the whole view and draw themselves, so the
+/// style of every trace comes straight from the stylesheet.
+pub struct WaveformTrace {
+    buffer: VecDeque<f32>,
+    max_samples: usize,
+    scope_trace: Option<Vec<f32>>,
+}
+
+/// The state of the triggered oscilloscope mode. The pre and post taps are written in lockstep,
+/// so the same index in both histories refers to the same moment in time.
+#[derive(Default)]
+struct Scope {
+    enabled: bool,
+    frozen: bool,
+    timebase_ms: f32,
+    read_pos: usize,
+    pre_history: Vec<f32>,
+    post_history: Vec<f32>,
+    /// The captured traces, one timebase long, with the trigger at `SCOPE_PRE_TRIGGER`.
+    pre_trace: Vec<f32>,
+    post_trace: Vec<f32>,
+    triggered: bool,
+}
+
+impl Scope {
+    fn update(&mut self, taps: &SignalTaps) -> bool {
+        if !self.enabled || self.frozen {
+            self.read_pos = taps.written();
+            return false;
+        }
+
+        let window = ((self.timebase_ms / 1000.0 * taps.sample_rate()).round() as usize).max(2);
+        self.read_pos =
+            taps.read_pre_post_since(self.read_pos, &mut self.pre_history, &mut self.post_history);
+
+        let max_history = window * 2;
+        if self.pre_history.len() > max_history {
+            let excess = self.pre_history.len() - max_history;
+            self.pre_history.drain(..excess);
+            self.post_history.drain(..excess);
+        }
+        if self.pre_history.len() < window {
+            return false;
+        }
+
+        // Look for the most recent rising edge that still has a full timebase worth of samples
+        // around it. Without a trigger we'll fall back to showing the latest samples.
+        let pre_trigger = (window as f32 * SCOPE_PRE_TRIGGER) as usize;
+        let last_start = self.pre_history.len() - window;
+        let trigger = (pre_trigger.max(1)..=last_start + pre_trigger)
+            .rev()
+            .find(|&i| {
+                self.pre_history[i - 1] < 0.0
+                    && self.pre_history[i] >= 0.0
+                    && self.pre_history[i.saturating_sub(pre_trigger.max(1))..i]
+                        .iter()
+                        .any(|&sample| sample < -SCOPE_TRIGGER_HYSTERESIS)
+            });
+        self.triggered = trigger.is_some();
+        let start = trigger.map_or(last_start, |i| i - pre_trigger);
+
+        self.pre_trace.clear();
+        self.pre_trace
+            .extend_from_slice(&self.pre_history[start..start + window]);
+        self.post_trace.clear();
+        self.post_trace
+            .extend_from_slice(&self.post_history[start..start + window]);
+
+        true
+    }
+
+    fn clear(&mut self) {
+        self.pre_history.clear();
+        self.post_history.clear();
+        self.pre_trace.clear();
+        self.post_trace.clear();
+        self.triggered = false;
+    }
 }
 
 impl WaveformView {
-    pub fn new<L1, L2>(
-        cx: &mut Context,
-        lens1: L1,
-        lens2: L2,
-        max_samples: usize,
-    ) -> Handle<'_, Self>
+    /// Creates an empty view that shows the last `max_samples` values of every trace.
+    pub fn new(cx: &mut Context, max_samples: usize) -> Handle<'_, Self> {
+        Self {
+            max_samples,
+            traces: Vec::new(),
+            scope: Scope {
+                timebase_ms: 10.0,
+                ..Default::default()
+            },
+        }
+        .build(cx, |_| {})
+    }
+
+    /// Hands the oscilloscope traces to the first two traces, or clears them again.
+    fn send_scope_traces(&self, cx: &mut EventContext) {
+        let mut scope_traces = [&self.scope.pre_trace, &self.scope.post_trace].into_iter();
+        for &trace in &self.traces {
+            let scope_trace = scope_traces.next().filter(|_| self.scope.enabled).cloned();
+            cx.emit_to(trace, WaveformTraceEvent::SetScopeTrace(scope_trace));
+        }
+    }
+
+    fn draw_scope_grid(&self, cx: &mut DrawContext, canvas: &Canvas, bounds: BoundingBox) {
+        // Time divisions and the trigger point
+        let mut grid = vg::Path::new();
+        for division in 1..SCOPE_DIVISIONS {
+            let x = bounds.x + bounds.w * division as f32 / SCOPE_DIVISIONS as f32;
+            grid.move_to((x, bounds.y));
+            grid.line_to((x, bounds.y + bounds.h));
+        }
+        let mut grid_paint = vg::Paint::default();
+        grid_paint.set_color(cx.border_color());
+        grid_paint.set_stroke_width(1.0);
+        grid_paint.set_style(vg::PaintStyle::Stroke);
+        canvas.draw_path(&grid, &grid_paint);
+
+        if self.scope.triggered {
+            let trigger_x = bounds.x + bounds.w * SCOPE_PRE_TRIGGER;
+            let mut trigger_line = vg::Path::new();
+            trigger_line.move_to((trigger_x, bounds.y));
+            trigger_line.line_to((trigger_x, bounds.y + bounds.h));
+            grid_paint.set_color(cx.font_color());
+            grid_paint.set_alpha_f(0.3);
+            canvas.draw_path(&trigger_line, &grid_paint);
+        }
+    }
+}
+
+impl WaveformTrace {
+    fn new<L>(cx: &mut Context, lens: L, max_samples: usize) -> Handle<'_, Self>
     where
-        L1: Lens<Target = f32>,
-        L2: Lens<Target = f32>,
+        L: Lens<Target = f32>,
     {
         Self {
-            buffer1: VecDeque::from(vec![0.0; max_samples]),
-            buffer2: VecDeque::from(vec![0.0; max_samples]),
+            buffer: VecDeque::from(vec![0.0; max_samples]),
             max_samples,
+            scope_trace: None,
         }
         .build(cx, |cx| {
-            Binding::new(cx, lens1, |cx, sample_lens1| {
-                let sample = sample_lens1.get(cx);
-                cx.emit(WaveformViewEvent::AddSample1(sample));
-            });
-            Binding::new(cx, lens2, |cx, sample_lens2| {
-                let sample = sample_lens2.get(cx);
-                cx.emit(WaveformViewEvent::AddSample2(sample));
+            Binding::new(cx, lens, |cx, sample_lens| {
+                let sample = sample_lens.get(cx);
+                cx.emit(WaveformTraceEvent::AddSample(sample));
             });
         })
     }
 
-    fn push_sample1(&mut self, sample: f32) {
-        if self.buffer1.len() >= self.max_samples {
-            self.buffer1.pop_front();
+    fn push_sample(&mut self, sample: f32) {
+        if self.buffer.len() >= self.max_samples {
+            self.buffer.pop_front();
         }
-        self.buffer1.push_back(sample);
+        self.buffer.push_back(sample);
     }
 
-    fn push_sample2(&mut self, sample: f32) {
-        if self.buffer2.len() >= self.max_samples {
-            self.buffer2.pop_front();
-        }
-        self.buffer2.push_back(sample);
+    fn stroke_paint(cx: &mut DrawContext) -> vg::Paint {
+        let mut stroke_paint = vg::Paint::default();
+        stroke_paint.set_color(cx.font_color());
+        stroke_paint.set_stroke_width(cx.border_width().max(1.5));
+        stroke_paint.set_style(vg::PaintStyle::Stroke);
+        stroke_paint.set_stroke_cap(vg::PaintCap::Round);
+        stroke_paint.set_stroke_join(vg::PaintJoin::Round);
+        stroke_paint.set_anti_alias(true);
+        stroke_paint
     }
-}
 
-impl View for WaveformView {
-    fn element(&self) -> Option<&'static str> {
-        Some("waveform-view")
-    }
+    fn draw_scope_trace(
+        &self,
+        cx: &mut DrawContext,
+        canvas: &Canvas,
+        bounds: BoundingBox,
+        trace: &[f32],
+    ) {
+        let mid_y = bounds.y + bounds.h / 2.0;
+        let half_h = bounds.h / 2.0;
 
-    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
-        event.map(|waveform_event, _| match waveform_event {
-            WaveformViewEvent::AddSample1(sample1) => {
-                self.push_sample1(*sample1);
-                cx.needs_redraw();
-            }
-            WaveformViewEvent::AddSample2(sample2) => {
-                self.push_sample2(*sample2);
-                cx.needs_redraw();
+        let mut path = vg::Path::new();
+        let x_step = bounds.w / (trace.len().max(2) - 1) as f32;
+        for (i, &sample) in trace.iter().enumerate() {
+            let x = bounds.x + i as f32 * x_step;
+            let y = mid_y - sample.clamp(-1.0, 1.0) * half_h;
+            if i == 0 {
+                path.move_to((x, y));
+            } else {
+                path.line_to((x, y));
             }
-        });
-    }
-
-    fn draw(&self, cx: &mut DrawContext, canvas: &Canvas) {
-        let bounds = cx.bounds();
-        if bounds.w == 0.0 || bounds.h == 0.0 {
-            return;
         }
 
-        let background_color = cx.background_color();
-        let stroke_color = cx.font_color();
-        let stroke_width = cx.border_width().max(1.5);
-
-        let mut bg_paint = vg::Paint::default();
-        bg_paint.set_color(background_color);
-        let rect = vg::Rect::from_xywh(bounds.x, bounds.y, bounds.w, bounds.h);
-        canvas.draw_rect(&rect, &bg_paint);
+        canvas.draw_path(&path, &Self::stroke_paint(cx));
+    }
 
+    fn draw_envelope(&self, cx: &mut DrawContext, canvas: &Canvas, bounds: BoundingBox) {
         let mid_y = bounds.y + bounds.h / 2.0;
         let half_h = bounds.h / 2.0;
         let x_step = bounds.w / (self.max_samples as f32 - 1.0);
 
         let mut wave_path = vg::Path::new();
 
-        //// buffer1
         // Top half
         let mut first = true;
-        for (i, &sample) in self.buffer1.iter().enumerate() {
+        for (i, &sample) in self.buffer.iter().enumerate() {
             let x = bounds.x + i as f32 * x_step;
             let sample_height = sample.abs().clamp(0.0, 1.0) * half_h;
             let y = mid_y - sample_height;
@@ -110,7 +247,7 @@ impl View for WaveformView {
         }
 
         // Bottom half, in reverse
-        for (i, &sample) in self.buffer1.iter().enumerate().rev() {
+        for (i, &sample) in self.buffer.iter().enumerate().rev() {
             let x = bounds.x + i as f32 * x_step;
             let sample_height = sample.abs().clamp(0.0, 1.0) * half_h;
             let y = mid_y + sample_height;
@@ -120,69 +257,178 @@ impl View for WaveformView {
         wave_path.close();
 
         // 渲染填充区域
-        let mut fill_paint = vg::Paint::default();
-        let fill_color = stroke_color;
-        fill_paint.set_dither(true);
-        fill_paint.set_color(fill_color);
-        fill_paint.set_alpha(114);
-        fill_paint.set_style(vg::PaintStyle::Fill);
-        fill_paint.set_anti_alias(true);
-        canvas.draw_path(&wave_path, &fill_paint);
+        let fill_color = cx.background_color();
+        if fill_color.a() > 0 {
+            let mut fill_paint = vg::Paint::default();
+            fill_paint.set_dither(true);
+            fill_paint.set_color(fill_color);
+            fill_paint.set_style(vg::PaintStyle::Fill);
+            fill_paint.set_anti_alias(true);
+            canvas.draw_path(&wave_path, &fill_paint);
+        }
 
         // 渲染轮廓线
-        let mut stroke_paint = vg::Paint::default();
-        stroke_paint.set_color(stroke_color);
-        stroke_paint.set_stroke_width(stroke_width);
-        stroke_paint.set_style(vg::PaintStyle::Stroke);
-        stroke_paint.set_stroke_cap(vg::PaintCap::Round);
-        stroke_paint.set_stroke_join(vg::PaintJoin::Round);
-        stroke_paint.set_anti_alias(true);
-        canvas.draw_path(&wave_path, &stroke_paint);
+        canvas.draw_path(&wave_path, &Self::stroke_paint(cx));
+    }
+}
 
-        //// buffer2
-        // Top half
-        let mut first = true;
-        for (i, &sample) in self.buffer2.iter().enumerate() {
-            let x = bounds.x + i as f32 * x_step;
-            let sample_height = sample.abs().clamp(0.0, 1.0) * half_h;
-            let y = mid_y - sample_height;
-            if first {
-                wave_path.move_to((x, y));
-                first = false;
-            } else {
-                wave_path.line_to((x, y));
+pub trait WaveformViewHandle {
+    /// Adds a trace showing the envelope of `lens`, styled by the CSS class `class`. Traces are
+    /// drawn in the order they were added.
+    fn trace<L>(self, class: &str, lens: L) -> Self
+    where
+        L: Lens<Target = f32>;
+
+    /// Adds a triggered oscilloscope mode that draws the raw pre and post signals from `taps`,
+    /// triggered on rising zero crossings of the pre signal. They use the styles of the first and
+    /// the second trace respectively. `timebase_ms` is the width of the
+    /// view in milliseconds, and `frozen` stops the traces from updating.
+    fn oscilloscope<T, E, B, F>(self, taps: T, enabled: E, timebase_ms: B, frozen: F) -> Self
+    where
+        T: Lens<Target = Arc<SignalTaps>>,
+        E: Lens<Target = bool>,
+        B: Lens<Target = f32>,
+        F: Lens<Target = bool>;
+}
+
+impl WaveformViewHandle for Handle<'_, WaveformView> {
+    fn trace<L>(mut self, class: &str, lens: L) -> Self
+    where
+        L: Lens<Target = f32>,
+    {
+        let entity = self.entity();
+        let mut max_samples = 0;
+        self = self.modify(|view| max_samples = view.max_samples);
+        let trace = self.context().with_current(entity, |cx| {
+            WaveformTrace::new(cx, lens, max_samples)
+                .class("waveform-trace")
+                .class(class)
+                .entity()
+        });
+
+        self.modify(|view| view.traces.push(trace))
+    }
+
+    fn oscilloscope<T, E, B, F>(mut self, taps: T, enabled: E, timebase_ms: B, frozen: F) -> Self
+    where
+        T: Lens<Target = Arc<SignalTaps>>,
+        E: Lens<Target = bool>,
+        B: Lens<Target = f32>,
+        F: Lens<Target = bool>,
+    {
+        let entity = self.entity();
+        let cx = self.context();
+        Binding::new(cx, taps.map(|taps| taps.pre.written()), move |cx, _| {
+            let taps = taps.get(cx);
+            cx.emit_to(entity, WaveformViewEvent::ScopeUpdate(taps));
+        });
+        Binding::new(cx, enabled, move |cx, enabled| {
+            cx.emit_to(entity, WaveformViewEvent::SetScopeEnabled(enabled.get(cx)));
+        });
+        Binding::new(cx, timebase_ms, move |cx, timebase_ms| {
+            cx.emit_to(
+                entity,
+                WaveformViewEvent::SetScopeTimebase(timebase_ms.get(cx)),
+            );
+        });
+        Binding::new(cx, frozen, move |cx, frozen| {
+            cx.emit_to(entity, WaveformViewEvent::SetScopeFrozen(frozen.get(cx)));
+        });
+
+        self
+    }
+}
+
+impl View for WaveformView {
+    fn element(&self) -> Option<&'static str> {
+        Some("waveform-view")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|waveform_event, _| match waveform_event {
+            WaveformViewEvent::ScopeUpdate(taps) => {
+                if self.scope.update(taps) {
+                    self.send_scope_traces(cx);
+                    cx.needs_redraw();
+                }
             }
-        }
+            WaveformViewEvent::SetScopeEnabled(enabled) => {
+                self.scope.enabled = *enabled;
+                self.scope.clear();
+                self.send_scope_traces(cx);
+                cx.needs_redraw();
+            }
+            WaveformViewEvent::SetScopeTimebase(timebase_ms) => {
+                self.scope.timebase_ms = *timebase_ms;
+                if !self.scope.frozen {
+                    self.scope.clear();
+                }
+            }
+            WaveformViewEvent::SetScopeFrozen(frozen) => {
+                self.scope.frozen = *frozen;
+            }
+        });
+    }
 
-        // Bottom half, in reverse
-        for (i, &sample) in self.buffer2.iter().enumerate().rev() {
-            let x = bounds.x + i as f32 * x_step;
-            let sample_height = sample.abs().clamp(0.0, 1.0) * half_h;
-            let y = mid_y + sample_height;
-            wave_path.line_to((x, y));
+    fn draw(&self, cx: &mut DrawContext, canvas: &Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
         }
 
-        wave_path.close();
+        let background_color = cx.background_color();
+        let mut bg_paint = vg::Paint::default();
+        bg_paint.set_color(background_color);
+        let rect = vg::Rect::from_xywh(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.draw_rect(&rect, &bg_paint);
 
-        // 渲染轮廓线
-        let mut stroke_paint = vg::Paint::default();
-        stroke_paint.set_color(Color::palegreen());
-        stroke_paint.set_alpha_f(0.5);
-        stroke_paint.set_stroke_width(stroke_width);
-        stroke_paint.set_style(vg::PaintStyle::Stroke);
-        stroke_paint.set_stroke_cap(vg::PaintCap::Round);
-        stroke_paint.set_stroke_join(vg::PaintJoin::Round);
-        stroke_paint.set_anti_alias(true);
-        canvas.draw_path(&wave_path, &stroke_paint);
+        if self.scope.enabled {
+            self.draw_scope_grid(cx, canvas, bounds);
+            return;
+        }
 
         // 绘制零位基准线
+        let mid_y = bounds.y + bounds.h / 2.0;
         let mut center_line = vg::Path::new();
         center_line.move_to((bounds.x, mid_y));
         center_line.line_to((bounds.x + bounds.w, mid_y));
         let mut center_paint = vg::Paint::default();
-        center_paint.set_color(Color::rgba(255, 255, 255, 20)); // 很淡的白色
+        center_paint.set_color(cx.border_color());
         center_paint.set_stroke_width(1.0);
         center_paint.set_style(vg::PaintStyle::Stroke);
         canvas.draw_path(&center_line, &center_paint);
     }
 }
+
+impl View for WaveformTrace {
+    fn element(&self) -> Option<&'static str> {
+        Some("waveform-trace")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|trace_event, _| match trace_event {
+            WaveformTraceEvent::AddSample(sample) => {
+                self.push_sample(*sample);
+                if self.scope_trace.is_none() {
+                    cx.needs_redraw();
+                }
+            }
+            WaveformTraceEvent::SetScopeTrace(scope_trace) => {
+                self.scope_trace = scope_trace.clone();
+                cx.needs_redraw();
+            }
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        match &self.scope_trace {
+            Some(trace) => self.draw_scope_trace(cx, canvas, bounds, trace),
+            None => self.draw_envelope(cx, canvas, bounds),
+        }
+    }
+}
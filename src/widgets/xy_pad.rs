@@ -0,0 +1,321 @@
+//! Controls two parameters at once, the horizontal axis drives one and the vertical axis the other.
+//! The axes use the parameters' normalized values, so skewed ranges get the same log-like scale as
+//! on their knobs.
+
+use nih_plug::prelude::{FloatParam, Param};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use vizia_plug::vizia::{prelude::*, vg};
+use vizia_plug::widgets::param_base::ParamWidgetBase;
+
+/// How long a point of the trail stays visible.
+const TRAIL_LENGTH: Duration = Duration::from_millis(1500);
+const TRAIL_FRAME_INTERVAL: Duration = Duration::from_millis(33);
+const HANDLE_RADIUS: f32 = 4.0;
+
+pub enum XyPadEvent {
+    SetX(f32),
+    SetY(f32),
+    SetTrail(bool),
+    /// Fades the trail out.
+    Tick,
+}
+
+pub struct XyPad {
+    x_param: ParamWidgetBase,
+    y_param: ParamWidgetBase,
+    /// The normalized values of both parameters.
+    value: (f32, f32),
+    /// The normalized positions of the grid lines, one at every power of ten.
+    x_grid: Vec<f32>,
+    y_grid: Vec<f32>,
+    dragging: bool,
+    /// Recent positions, oldest first. Host automation leaves a trail as well.
+    trail: VecDeque<((f32, f32), Instant)>,
+    show_trail: bool,
+}
+
+impl XyPad {
+    pub fn new<L, Params, FMapX, FMapY>(
+        cx: &mut Context,
+        params: L,
+        x_param: FMapX,
+        y_param: FMapY,
+    ) -> Handle<'_, Self>
+    where
+        L: Lens<Target = Params> + Clone,
+        Params: 'static,
+        FMapX: Fn(&Params) -> &FloatParam + Copy + 'static,
+        FMapY: Fn(&Params) -> &FloatParam + Copy + 'static,
+    {
+        let x_grid = params
+            .clone()
+            .map(move |params| decade_grid(x_param(params)))
+            .get(cx);
+        let y_grid = params
+            .clone()
+            .map(move |params| decade_grid(y_param(params)))
+            .get(cx);
+
+        Self {
+            x_param: ParamWidgetBase::new(cx, params.clone(), x_param),
+            y_param: ParamWidgetBase::new(cx, params.clone(), y_param),
+            value: (0.0, 0.0),
+            x_grid,
+            y_grid,
+            dragging: false,
+            trail: VecDeque::new(),
+            show_trail: false,
+        }
+        .build(cx, move |cx| {
+            Binding::new(
+                cx,
+                params
+                    .clone()
+                    .map(move |params| x_param(params).unmodulated_normalized_value()),
+                |cx, value| cx.emit(XyPadEvent::SetX(value.get(cx))),
+            );
+            Binding::new(
+                cx,
+                params.map(move |params| y_param(params).unmodulated_normalized_value()),
+                |cx, value| cx.emit(XyPadEvent::SetY(value.get(cx))),
+            );
+
+            let trail_timer = cx.add_timer(TRAIL_FRAME_INTERVAL, None, |cx, action| {
+                if let TimerAction::Tick(_) = action {
+                    cx.emit(XyPadEvent::Tick);
+                }
+            });
+            cx.start_timer(trail_timer);
+        })
+    }
+
+    /// Starts a gesture on both parameters at the same time.
+    fn begin_set_parameters(&self, cx: &mut EventContext) {
+        self.x_param.begin_set_parameter(cx);
+        self.y_param.begin_set_parameter(cx);
+    }
+
+    fn set_normalized_values(&self, cx: &mut EventContext, (x, y): (f32, f32)) {
+        self.x_param.set_normalized_value(cx, x);
+        self.y_param.set_normalized_value(cx, y);
+    }
+
+    fn end_set_parameters(&self, cx: &mut EventContext) {
+        self.x_param.end_set_parameter(cx);
+        self.y_param.end_set_parameter(cx);
+    }
+
+    fn reset(&self, cx: &mut EventContext) {
+        self.begin_set_parameters(cx);
+        self.set_normalized_values(
+            cx,
+            (
+                self.x_param.default_normalized_value(),
+                self.y_param.default_normalized_value(),
+            ),
+        );
+        self.end_set_parameters(cx);
+    }
+
+    /// The normalized values under the cursor, with the lowest values in the bottom left.
+    fn cursor_value(&self, cx: &EventContext) -> (f32, f32) {
+        let bounds = cx.bounds();
+        let x = (cx.mouse().cursor_x - bounds.x) / bounds.w;
+        let y = 1.0 - (cx.mouse().cursor_y - bounds.y) / bounds.h;
+        (x.clamp(0.0, 1.0), y.clamp(0.0, 1.0))
+    }
+
+    fn push_trail_point(&mut self) {
+        if self.show_trail {
+            self.trail.push_back((self.value, Instant::now()));
+        }
+    }
+}
+
+/// The normalized values of the powers of ten within the parameter's range.
+fn decade_grid(param: &FloatParam) -> Vec<f32> {
+    let min = param.preview_plain(0.0);
+    let max = param.preview_plain(1.0);
+    if min <= 0.0 {
+        return Vec::new();
+    }
+
+    (min.log10().ceil() as i32..=max.log10().floor() as i32)
+        .map(|exponent| 10f32.powi(exponent))
+        .filter(|&value| value > min && value < max)
+        .map(|value| param.preview_normalized(value))
+        .collect()
+}
+
+impl View for XyPad {
+    fn element(&self) -> Option<&'static str> {
+        Some("xy-pad")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|pad_event, meta| match pad_event {
+            XyPadEvent::SetX(x) => {
+                self.value.0 = *x;
+                self.push_trail_point();
+                cx.needs_redraw();
+                meta.consume();
+            }
+            XyPadEvent::SetY(y) => {
+                self.value.1 = *y;
+                self.push_trail_point();
+                cx.needs_redraw();
+                meta.consume();
+            }
+            XyPadEvent::SetTrail(show_trail) => {
+                self.show_trail = *show_trail;
+                self.trail.clear();
+                cx.needs_redraw();
+                meta.consume();
+            }
+            XyPadEvent::Tick => {
+                if !self.trail.is_empty() {
+                    let now = Instant::now();
+                    while self
+                        .trail
+                        .front()
+                        .is_some_and(|(_, time)| now - *time > TRAIL_LENGTH)
+                    {
+                        self.trail.pop_front();
+                    }
+                    cx.needs_redraw();
+                }
+                meta.consume();
+            }
+        });
+
+        event.map(|window_event, meta| match window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                if cx.modifiers().alt() {
+                    self.reset(cx);
+                } else {
+                    cx.capture();
+                    cx.set_active(true);
+                    self.dragging = true;
+
+                    self.begin_set_parameters(cx);
+                    let value = self.cursor_value(cx);
+                    self.set_normalized_values(cx, value);
+                }
+                meta.consume();
+            }
+            WindowEvent::MouseDoubleClick(MouseButton::Left) => {
+                self.reset(cx);
+                meta.consume();
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if self.dragging {
+                    self.dragging = false;
+                    cx.release();
+                    cx.set_active(false);
+                    self.end_set_parameters(cx);
+                    meta.consume();
+                }
+            }
+            WindowEvent::MouseMove(_, _) => {
+                if self.dragging {
+                    let value = self.cursor_value(cx);
+                    self.set_normalized_values(cx, value);
+                    meta.consume();
+                }
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        // The face, the grid and frame, and the handle and trail come from the stylesheet
+        let background_color = cx.background_color();
+        let border_color = cx.border_color();
+        let font_color = cx.font_color();
+
+        let to_point = |(x, y): (f32, f32)| {
+            vg::Point::new(bounds.x + x * bounds.w, bounds.y + (1.0 - y) * bounds.h)
+        };
+
+        let mut paint = vg::Paint::default();
+        paint.set_color(background_color);
+        let rect = vg::Rect::from_xywh(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.draw_rect(&rect, &paint);
+
+        let mut paint_grid = vg::Paint::default();
+        paint_grid.set_color(border_color);
+        paint_grid.set_alpha_f(paint_grid.alpha_f() * 0.3);
+        paint_grid.set_stroke_width(1.0);
+        paint_grid.set_style(vg::PaintStyle::Stroke);
+        for &x in &self.x_grid {
+            canvas.draw_line(to_point((x, 0.0)), to_point((x, 1.0)), &paint_grid);
+        }
+        for &y in &self.y_grid {
+            canvas.draw_line(to_point((0.0, y)), to_point((1.0, y)), &paint_grid);
+        }
+
+        let mut paint_line = vg::Paint::default();
+        paint_line.set_color(font_color);
+        paint_line.set_stroke_width(1.5);
+        paint_line.set_stroke_cap(vg::PaintCap::Round);
+        paint_line.set_style(vg::PaintStyle::Stroke);
+        paint_line.set_anti_alias(true);
+
+        // Every segment of the trail fades with its age
+        let now = Instant::now();
+        let base_alpha = paint_line.alpha_f();
+        for (from, to) in self.trail.iter().zip(self.trail.iter().skip(1)) {
+            let age = (now - to.1).as_secs_f32() / TRAIL_LENGTH.as_secs_f32();
+            paint_line.set_alpha_f(base_alpha * (1.0 - age).clamp(0.0, 1.0) * 0.6);
+            canvas.draw_line(to_point(from.0), to_point(to.0), &paint_line);
+        }
+
+        // Crosshair and handle at the current values
+        let handle = to_point(self.value);
+        paint_line.set_alpha_f(base_alpha * 0.4);
+        paint_line.set_stroke_width(1.0);
+        canvas.draw_line(
+            vg::Point::new(handle.x, bounds.y),
+            vg::Point::new(handle.x, bounds.y + bounds.h),
+            &paint_line,
+        );
+        canvas.draw_line(
+            vg::Point::new(bounds.x, handle.y),
+            vg::Point::new(bounds.x + bounds.w, handle.y),
+            &paint_line,
+        );
+
+        let mut paint_handle = vg::Paint::default();
+        paint_handle.set_color(font_color);
+        paint_handle.set_style(vg::PaintStyle::Fill);
+        paint_handle.set_anti_alias(true);
+        canvas.draw_circle(handle, HANDLE_RADIUS, &paint_handle);
+
+        let mut paint_frame = vg::Paint::default();
+        paint_frame.set_color(border_color);
+        paint_frame.set_stroke_width(1.0);
+        paint_frame.set_style(vg::PaintStyle::Stroke);
+        canvas.draw_rect(&rect, &paint_frame);
+    }
+}
+
+pub trait XyPadHandle {
+    /// Shows the path of the recent changes.
+    fn trail<L: Lens<Target = bool>>(self, lens: L) -> Self;
+}
+
+impl XyPadHandle for Handle<'_, XyPad> {
+    fn trail<L: Lens<Target = bool>>(mut self, lens: L) -> Self {
+        let entity = self.entity();
+        Binding::new(self.context(), lens, move |cx, show_trail| {
+            cx.emit_to(entity, XyPadEvent::SetTrail(show_trail.get(cx)));
+        });
+        self
+    }
+}